@@ -0,0 +1,397 @@
+use crate::{
+    ctx::Context as PlatformContext,
+    disk::{
+        activity::Activity,
+        bay::{self as bay_record, BayRecord},
+        health::{self, ErrorLeds},
+    },
+    utils,
+};
+use anyhow::{Context, Result};
+use pico_args::Arguments;
+use qute_ctrl::{
+    disk::{self, Bay, Disk},
+    DiskDrive, LedControl,
+};
+use std::{
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// interval of remapping bays to disks, to follow hot-swap
+const REMAP_INTERVAL: Duration = Duration::from_secs(10);
+
+pub fn run(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    let cmd = args.subcommand().ok().flatten().unwrap_or_default();
+    match cmd.as_str() {
+        "activity" => return process_activity(args, ctx),
+        "health" => return process_health(args, ctx),
+        "list" => return process_list(args, ctx),
+        "locate" => return process_locate(args, ctx),
+        "power" => return process_power(args, ctx),
+        _ => {}
+    }
+    print_help();
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        r"qute disk [OPTIONS] [COMMANDS]
+Disk bays
+
+OPTIONS:
+  -h, --help                 Print this help text.
+
+COMMANDS:
+  activity                   Drive activity LEDs of bays from disk I/O
+  health                     Check SMART health of disks, light error LEDs of failing ones
+  list                       List drive bays and the disks in them
+  locate                     Blink the ident LED of a bay
+  power                      Power a bay off or on, for hot-swap
+"
+    );
+}
+
+fn process_list(_args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute disk list [OPTIONS]
+
+List drive bays and the disks in them, followed by disks out of bays, e.g. NVMe.
+
+PORT is the port id of EC driving LEDs and power of the bay, ATA the libata port of the bay.
+LINK is the SATA link speed, down for an empty bay or one powered off. EC reads back neither
+power nor LEDs of bays, so POWER and LEDS are as last set by qute; POWER is on whenever the link
+is up.
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let chip = ctx.get_platform()?;
+    let bays = chip.bay_disks()?;
+    let records = bay_record::load_all().unwrap_or_else(|e| {
+        warn!("{:#}", e);
+        vec![]
+    });
+    let header = columns("DEVICE", "TYPE", "MODEL", "SERIAL");
+    print_row("BAY", "PORT", "ATA", "LINK", "POWER", "LEDS", &header);
+    for (bay, disk) in bays.iter() {
+        let record = records.iter().find(|v| v.port_id == bay.port_id);
+        print_bay(bay, record, disk.as_ref());
+    }
+    for disk in disk::list_disks()? {
+        if !bays.iter().any(|(_, v)| v.as_ref() == Some(&disk)) {
+            print_row("-", "-", "-", "-", "-", "-", &describe(&disk));
+        }
+    }
+    if bays.iter().any(|(bay, _)| !bay.verified) {
+        println!("ports of bays are guessed from the order of libata ports, set `bays` in the config file");
+    }
+    Ok(())
+}
+
+fn print_bay(bay: &Bay, record: Option<&BayRecord>, disk: Option<&Disk>) {
+    let link = disk::link_speed_in(Path::new("/sys"), bay.ata_port);
+    let power = match (&link, record.and_then(|v| v.power)) {
+        (Some(_), _) | (None, Some(true)) => "on",
+        (None, Some(false)) => "off",
+        (None, None) => "-",
+    };
+    let leds = record
+        .map(|v| v.leds())
+        .unwrap_or_else(|| String::from("-"));
+    let device = match disk {
+        Some(disk) => describe(disk),
+        // a link without a block device, e.g. detached but still powered
+        None if link.is_some() => String::from("not attached"),
+        None => String::from("empty"),
+    };
+    print_row(
+        &bay.index.to_string(),
+        &bay.port_id.to_string(),
+        &format!("ata{}", bay.ata_port),
+        link.as_deref().unwrap_or("down"),
+        power,
+        &leds,
+        &device,
+    );
+}
+
+/// a row of `disk list`; `port` is the port id of EC, `device` the DEVICE, TYPE, MODEL and
+/// SERIAL columns
+fn print_row(bay: &str, port: &str, ata: &str, link: &str, power: &str, leds: &str, device: &str) {
+    println!(
+        "{:<5}{:<6}{:<6}{:<10}{:<7}{:<13}{}",
+        bay, port, ata, link, power, leds, device
+    );
+}
+
+fn columns(device: &str, kind: &str, model: &str, serial: &str) -> String {
+    format!("{:<14}{:<6}{:<32}{}", device, kind, model, serial)
+}
+
+fn describe(disk: &Disk) -> String {
+    columns(
+        &disk.dev.display().to_string(),
+        &disk.kind.to_string(),
+        disk.model.as_deref().unwrap_or("-"),
+        disk.serial.as_deref().unwrap_or("-"),
+    )
+}
+
+fn process_locate(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute disk locate [OPTIONS] <DEVICE|BAY>
+
+Blink the ident LED of the bay holding the disk, e.g. /dev/sdb, or of the bay number, until
+interrupted by Ctrl-C or timeout.
+
+OPTIONS:
+  -t, --timeout              Seconds to blink, default until interrupted.
+      --off                  Turn the ident LED off and exit.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let timeout: Option<u64> = args
+        .opt_value_from_str(["-t", "--timeout"])
+        .with_context(|| "invalid input for timeout")?;
+    let off = args.contains("--off");
+    let target: String = args
+        .free_from_str()
+        .with_context(|| "disk locate: require a device or bay number")?;
+    let mut chip = ctx.get_platform()?;
+    let bay = find_bay(&chip, &target)?;
+    if off {
+        chip.set_disk_ident_led(bay.port_id, false)?;
+        bay_record::record(bay.port_id, |v| v.ident = false);
+        println!("√ ident LED of bay {} was turned off", bay.index);
+        return Ok(());
+    }
+    utils::catch_terminate();
+    chip.set_disk_ident_led(bay.port_id, true)?;
+    bay_record::record(bay.port_id, |v| v.ident = true);
+    println!(
+        "√ ident LED of bay {} is blinking, Ctrl-C to stop",
+        bay.index
+    );
+    let start = Instant::now();
+    while !utils::is_terminated() {
+        if let Some(timeout) = timeout {
+            if start.elapsed() >= Duration::from_secs(timeout) {
+                break;
+            }
+        }
+        sleep(Duration::from_millis(200));
+    }
+    chip.set_disk_ident_led(bay.port_id, false)?;
+    bay_record::record(bay.port_id, |v| v.ident = false);
+    println!("√ ident LED of bay {} was turned off", bay.index);
+    Ok(())
+}
+
+/// bay by number, or by the disk in it
+fn find_bay<T: DiskDrive>(chip: &T, target: &str) -> Result<Bay> {
+    if let Ok(index) = target.parse::<u8>() {
+        return chip
+            .bays()?
+            .into_iter()
+            .find(|v| v.index == index)
+            .ok_or_else(|| anyhow!("no bay {}", index));
+    }
+    let dev = PathBuf::from(target);
+    chip.find_bay(&dev)
+        .with_context(|| format!("invalid device {}", target))?
+        .map(|(bay, _)| bay)
+        .ok_or_else(|| anyhow!("{} is not in a drive bay", target))
+}
+
+fn process_power(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute disk power [OPTIONS] <on|off> <BAY>
+
+Power a bay off or on, for hot-swap.
+
+off: refuse if any partition of the disk is mounted, in an active md array, in use as swap or
+held by device mapper; otherwise flush and detach the disk, then cut power of the bay. Bays
+whose EC port id or libata port is guessed are refused; set `bays` in the config file once
+checked with `qute disk locate` and `qute disk list`. A bay where no disk is found is refused
+unless --force.
+on: restore power of the bay, rescan its SATA host and wait for the disk to appear.
+
+OPTIONS:
+  -y, --yes                  Do not ask for confirmation.
+  -f, --force                Power off a bay even if no disk is found in it.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let yes = args.contains(["-y", "--yes"]);
+    let force = args.contains(["-f", "--force"]);
+    let action: String = args
+        .free_from_str()
+        .with_context(|| "disk power: require on or off")?;
+    let index: u8 = args
+        .free_from_str()
+        .with_context(|| "disk power: require a bay number")?;
+    let chip = ctx.get_platform()?;
+    let bay = find_bay(&chip, &index.to_string())?;
+    match action.as_str() {
+        "off" => {
+            if !bay.verified {
+                return Err(anyhow!(
+                    "ports of bay {} are guessed; check them with `qute disk locate` and \
+                     `qute disk list`, then set `bays` in the config file",
+                    bay.index
+                ));
+            }
+            let disk = chip
+                .bay_disks()?
+                .into_iter()
+                .find(|(v, _)| *v == bay)
+                .and_then(|(_, disk)| disk);
+            let prompt = match disk {
+                Some(ref disk) => format!(
+                    "power off bay {} with {} {}?",
+                    bay.index,
+                    disk.dev.display(),
+                    disk.model.as_deref().unwrap_or_default()
+                ),
+                None if force => format!("power off bay {}, where no disk is found?", bay.index),
+                None => {
+                    return Err(anyhow!(
+                        "no disk found in bay {}, use --force to power it off anyway",
+                        bay.index
+                    ))
+                }
+            };
+            if !yes && !utils::confirm(&prompt)? {
+                return Ok(());
+            }
+            chip.power_off_bay(&bay, force)?;
+            bay_record::record(bay.port_id, |v| v.power = Some(false));
+            println!("√ bay {} was powered off", bay.index);
+        }
+        "on" => {
+            // powered even if the disk does not show up
+            let res = chip.power_on_bay(&bay);
+            bay_record::record(bay.port_id, |v| v.power = Some(true));
+            let disk = res?;
+            println!(
+                "√ bay {} was powered on, {} is ready",
+                bay.index,
+                disk.dev.display()
+            );
+        }
+        _ => return Err(anyhow!("disk power: invalid action {}", action)),
+    }
+    Ok(())
+}
+
+fn process_activity(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute disk activity [OPTIONS]
+
+Blink activity LEDs of bays on disk I/O, read from /proc/diskstats, until interrupted by Ctrl-C.
+For systems where nothing else drives the LEDs, i.e. not running QTS.
+
+OPTIONS:
+      --interval             Sample interval in ms, default 100.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let interval: u64 = args
+        .opt_value_from_str("--interval")
+        .with_context(|| "invalid input for interval")?
+        .unwrap_or(100);
+    let chip = ctx.get_platform()?;
+    let bay_ports = || -> Result<Vec<(u8, String)>> {
+        let res = chip
+            .bay_disks()?
+            .into_iter()
+            .filter_map(|(bay, disk)| disk.map(|v| (bay.port_id, v.name)))
+            .collect();
+        Ok(res)
+    };
+    let ports = bay_ports()?;
+    // start from dark LEDs
+    let all_off: Vec<(u8, bool)> = chip.bays()?.iter().map(|v| (v.port_id, false)).collect();
+    chip.set_disk_active_leds(&all_off)?;
+    let mut leds = Activity::new(ports);
+    utils::catch_terminate();
+    let mut remapped = Instant::now();
+    let mut res = Ok(());
+    while !utils::is_terminated() {
+        if remapped.elapsed() >= REMAP_INTERVAL {
+            remapped = Instant::now();
+            match bay_ports() {
+                Ok(ports) => {
+                    let changes = leds.set_ports(ports);
+                    chip.set_disk_active_leds(&changes)?;
+                }
+                Err(e) => warn!("failed to map bays: {:#}", e),
+            }
+        }
+        let stats = match disk::read_diskstats(Path::new("/proc")) {
+            Ok(v) => v,
+            Err(e) => {
+                res = Err(e.into());
+                break;
+            }
+        };
+        let changes = leds.update(&stats);
+        // one EC lock for all changes of the sample
+        chip.set_disk_active_leds(&changes)?;
+        sleep(Duration::from_millis(interval));
+    }
+    chip.set_disk_active_leds(&leds.off())?;
+    res
+}
+
+fn process_health(_args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute disk health [OPTIONS]
+
+Check SMART health of disks in bays: overall-health self-assessment, pending, uncorrectable and
+reallocated sectors, NVMe critical warnings. Error LEDs of failing disks are lit, and cleared once
+the disks are replaced. Disks in standby are skipped, not woken up.
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let chip = ctx.get_platform()?;
+    let mut leds = ErrorLeds::default();
+    let reports = health::check_bays(&chip, &mut leds)?;
+    println!("BAY  DEVICE        HEALTH    DETAILS");
+    for (bay, disk, report) in reports {
+        let (status, details) = match report {
+            Ok(Some(report)) => (report.health.to_string(), report.reasons.join(", ")),
+            Ok(None) => (String::from("-"), String::from("in standby, skipped")),
+            Err(e) => (String::from("unknown"), format!("{:#}", e)),
+        };
+        println!(
+            "{:<5}{:<14}{:<10}{}",
+            bay.index,
+            disk.dev.display().to_string(),
+            status,
+            details
+        );
+    }
+    Ok(())
+}
@@ -1,6 +1,15 @@
-use crate::ctx::Context as PlatformContext;
+use crate::{
+    ctx::Context as PlatformContext,
+    fan::{
+        calibration::{self, Calibration},
+        health::Evaluator,
+        wear::Tracker,
+    },
+    utils,
+};
 use anyhow::{Context, Result};
-use qute_ctrl::FanControl;
+use qute_ctrl::{platform::Platform, FanControl, FanCurve, FanMode, RpmHold};
+use std::{thread::sleep, time::Duration};
 
 use pico_args::Arguments;
 
@@ -10,6 +19,10 @@ pub fn run(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
         "pwm" => return process_pwm(args, ctx),
         "speed" => return process_speed(args, ctx),
         "status" => return process_status(args, ctx),
+        "mode" => return process_mode(args, ctx),
+        "curve" => return process_curve(args, ctx),
+        "calibrate" => return process_calibrate(args, ctx),
+        "health" => return process_health(args, ctx),
         _ => {}
     }
     print_help();
@@ -28,6 +41,10 @@ COMMANDS:
   pwm                        Get or set pwm
   speed                      Get current speed
   status                      Get fan status
+  mode                       Get or set fan mode
+  curve                      Show or set the fan curve of EC firmware
+  calibrate                  Measure PWM to RPM curve of fans
+  health                     Report fan wear recorded by monitor
 "
     );
 }
@@ -51,7 +68,7 @@ OPTIONS:
         .opt_value_from_str(["-i", "--index"])
         .with_context(|| "invalid input for index")?
         .unwrap_or(0);
-    let config = ctx.get_config();
+    let config = ctx.get_config()?;
     if index >= config.fan.len() as u8 {
         return Err(anyhow!(format!("fan pwm: invalid fan index {}", index)));
     }
@@ -61,8 +78,12 @@ OPTIONS:
         .with_context(|| "invalid input for value")?;
     if let Some(val) = val {
         //set val
-        chip.set_fan_speed(index, val)?;
-        println!("√ PWM of fan {} was set to {}", index, val);
+        let cal = calibration::load(index)?;
+        let pwm = calibration::set_pwm(&chip, index, val, cal.as_ref())?;
+        if pwm != val {
+            println!("PWM {} stalls fan {}, raised to {}", val, index, pwm);
+        }
+        println!("√ PWM of fan {} was set to {}", index, pwm);
     } else {
         //get val
         let val = chip.get_fan_pwm(index)?;
@@ -76,10 +97,16 @@ fn process_speed(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
         println!(
             r"qute fan speed [OPTIONS]
 
-Get fan speed in RPM
+Get fan speed in RPM, or hold the fan at a target speed
+
+With a target, PWM is adjusted as the speed drifts, e.g. with dust or temperature, until
+interrupted by Ctrl-C; the fan is then handed back to EC firmware. PWM is kept within min_pwm and
+max_pwm of the config file, and above the stall floor of a calibrated fan, see `fan calibrate`.
 
 OPTIONS:
   -i, --index                   Fan Index. Optional.
+  -t, --target                  Target speed in RPM to hold.
+      --tolerance               Allowed deviation from target speed in RPM, default 50
   -h, --help                    Print this help text.
 "
         );
@@ -89,10 +116,42 @@ OPTIONS:
         .opt_value_from_str(["-i", "--index"])
         .with_context(|| "invalid input for index")?
         .unwrap_or(0);
-    let config = ctx.get_config();
+    let config = ctx.get_config()?;
     if index >= config.fan.len() as u8 {
         return Err(anyhow!(format!("fan speed: invalid fan index {}", index)));
     }
+    let target: Option<u16> = args
+        .opt_value_from_str(["-t", "--target"])
+        .with_context(|| "invalid input for target")?;
+    if let Some(target) = target {
+        let tolerance: u16 = args
+            .opt_value_from_str("--tolerance")
+            .with_context(|| "invalid input for tolerance")?
+            .unwrap_or(50);
+        let fan = &config.fan[index as usize];
+        if target < fan.min_speed || target > fan.max_speed {
+            return Err(anyhow!(format!(
+                "fan speed: target {} RPM out of range [{}-{}]",
+                target, fan.min_speed, fan.max_speed
+            )));
+        }
+        let cal = calibration::load(index)?;
+        let min_pwm = fan.min_pwm.max(cal.as_ref().map_or(0, |v| v.sustain_pwm));
+        let hold = RpmHold::new(target, tolerance, min_pwm, fan.max_pwm);
+        let chip = ctx.get_platform()?;
+        utils::catch_terminate();
+        println!(
+            "holding fan {} at {}±{} RPM, Ctrl-C to stop",
+            index, target, tolerance
+        );
+        let res = hold_rpm(&chip, index, hold, cal.as_ref());
+        // hand the fan back to EC fw, so that it will not stay at the last pwm
+        let restored = chip.set_fan_mode(index, FanMode::Auto);
+        if restored.is_ok() {
+            println!("√ mode of fan {} was set to {}", index, FanMode::Auto);
+        }
+        return res.and(restored.map_err(Into::into));
+    }
     //get speed
     let chip = ctx.get_platform()?;
     let speed = chip.get_fan_speed(index)?;
@@ -100,12 +159,44 @@ OPTIONS:
     Ok(())
 }
 
+/// time for the fan speed to settle after PWM changed
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// step PWM of the fan to hold its speed, until terminated
+fn hold_rpm(chip: &Platform, fan: u8, hold: RpmHold, cal: Option<&Calibration>) -> Result<()> {
+    // start within the range; a stopped fan is kicked
+    let mut pwm = calibration::set_pwm(chip, fan, hold.clamp(chip.get_fan_pwm(fan)?), cal)?;
+    let mut held = false;
+    while !utils::is_terminated() {
+        sleep(SETTLE_TIME);
+        let (rpm, next) = chip.set_fan_rpm_within(fan, hold)?;
+        if hold.holds(rpm) != held {
+            held = !held;
+            if held {
+                println!("√ fan {} runs at {} RPM, pwm: {}", fan, rpm, pwm);
+            } else {
+                println!("fan {} drifted to {} RPM, pwm: {}", fan, rpm, pwm);
+            }
+        }
+        if next == pwm && !held {
+            // at the end of the PWM range
+            warn!(
+                "fan {} can not reach the target, {} RPM at pwm {}",
+                fan, rpm, pwm
+            );
+        }
+        pwm = next;
+    }
+    Ok(())
+}
+
 fn process_status(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
     if ctx.get_opts().help {
         println!(
             r"qute fan status [OPTIONS]
 
-Get fan status
+Get fan status reported by EC, and fan health evaluated from fan speed and PWM:
+  healthy, degraded (slower than expected), stalled (not spinning) or missing
 
 OPTIONS:
   -i, --index                   Fan Index. Optional.
@@ -118,13 +209,234 @@ OPTIONS:
         .opt_value_from_str::<_, u8>(["-i", "--index"])
         .with_context(|| "invalid input for index")?
         .unwrap_or(0);
-    let config = ctx.get_config();
+    let config = ctx.get_config()?;
     if index >= config.fan.len() as u8 {
         return Err(anyhow!(format!("fan status: invalid fan index {}", index)));
     }
     //get speed
     let chip = ctx.get_platform()?;
     let val = chip.get_fan_status(index)?;
-    println!("fan {} status: {}", index, val);
+    let health = Evaluator::new(calibration::load_all()?).evaluate(&chip, index)?;
+    println!("fan {} status: {} [{}]", index, val, health);
+    Ok(())
+}
+
+fn process_mode(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute fan mode [OPTIONS] [auto|manual]
+
+Get or set fan mode
+
+OPTIONS:
+  -i, --index                   Fan Index. Optional.
+  -h, --help                    Print this help text.
+
+MODES:
+  auto                          The fan is driven by the fan curve of EC firmware
+  manual                        The fan is driven by PWM, see `qute fan pwm`
+
+DEFAULT:
+  Get current fan mode
+"
+        );
+        return Ok(());
+    }
+    let index: u8 = args
+        .opt_value_from_str(["-i", "--index"])
+        .with_context(|| "invalid input for index")?
+        .unwrap_or(0);
+    let config = ctx.get_config()?;
+    if index >= config.fan.len() as u8 {
+        return Err(anyhow!(format!("fan mode: invalid fan index {}", index)));
+    }
+    let mode: Option<FanMode> = args
+        .opt_free_from_str()
+        .with_context(|| "invalid input for mode")?;
+    let chip = ctx.get_platform()?;
+    if let Some(mode) = mode {
+        chip.set_fan_mode(index, mode)?;
+        println!("√ mode of fan {} was set to {}", index, mode);
+    } else {
+        let mode = chip.get_fan_mode(index)?;
+        println!("fan {} mode: {}", index, mode);
+    }
+    Ok(())
+}
+
+fn process_curve(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute fan curve [OPTIONS] [show|set]
+
+Show or set the fan curve of EC firmware, which drives the fan in auto mode.
+Only the slope of the curve is known; no temperature thresholds of it are. How EC
+firmware maps the slope to PWM is not documented, so check the fan speed after setting
+it, e.g. with `fan speed`. Fans 0-4 share one slope.
+
+OPTIONS:
+  -i, --index                   Fan Index. Optional.
+      --slope                   Slope [0-255], required by set
+  -h, --help                    Print this help text.
+
+COMMANDS:
+  show                          Show current fan curve, default
+  set                           Set fan curve
+"
+        );
+        return Ok(());
+    }
+    let cmd = args.subcommand().ok().flatten().unwrap_or_default();
+    let index: u8 = args
+        .opt_value_from_str(["-i", "--index"])
+        .with_context(|| "invalid input for index")?
+        .unwrap_or(0);
+    let config = ctx.get_config()?;
+    if index >= config.fan.len() as u8 {
+        return Err(anyhow!(format!("fan curve: invalid fan index {}", index)));
+    }
+    let chip = ctx.get_platform()?;
+    match cmd.as_str() {
+        "set" => {
+            let slope: u8 = args
+                .value_from_str("--slope")
+                .with_context(|| "invalid input for slope")?;
+            let curve = FanCurve { slope };
+            chip.set_fan_curve(index, curve)?;
+            println!("√ curve of fan {} was set to {}", index, curve);
+            if chip.get_fan_mode(index)? != FanMode::Auto {
+                println!(
+                    "fan {} is in manual mode, run `qute fan mode auto` to apply",
+                    index
+                );
+            }
+        }
+        "show" | "" => {
+            let curve = chip.get_fan_curve(index)?;
+            let mode = chip.get_fan_mode(index)?;
+            println!("fan {} curve: {} [mode: {}]", index, curve, mode);
+        }
+        _ => return Err(anyhow!(format!("fan curve: invalid command {}", cmd))),
+    }
+    Ok(())
+}
+
+fn process_calibrate(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute fan calibrate [OPTIONS]
+
+Sweep fan PWM to measure the PWM to RPM curve, the minimal PWM to start the fan,
+the minimal PWM to keep it spinning and the maximum RPM. It takes a few minutes
+per fan, and the fan stops for a short while.
+
+The result is stored in /var/lib/qute/fan-calibration.json, then `fan pwm` and
+`monitor` avoid PWMs where the fan stalls, and kick-start the fan if it stopped.
+
+OPTIONS:
+  -i, --index                   Fan Index. Optional, all fans by default.
+  -h, --help                    Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let index: Option<u8> = args
+        .opt_value_from_str(["-i", "--index"])
+        .with_context(|| "invalid input for index")?;
+    let config = ctx.get_config()?;
+    let fans: Vec<u8> = match index {
+        Some(idx) if idx >= config.fan.len() as u8 => {
+            return Err(anyhow!(format!("fan calibrate: invalid fan index {}", idx)));
+        }
+        Some(idx) => vec![idx],
+        None => (0..config.fan.len() as u8).collect(),
+    };
+    let chip = ctx.get_platform()?;
+    utils::catch_terminate();
+    for index in fans {
+        println!("calibrating fan {}...", index);
+        let cal = Calibration::run(&chip, index)?;
+        calibration::save(&cal)?;
+        println!(
+            "√ fan {} was calibrated: max {} RPM, stalls below pwm {}, starts from pwm {}",
+            index, cal.max_rpm, cal.sustain_pwm, cal.start_pwm
+        );
+    }
+    Ok(())
+}
+
+fn process_health(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute fan health [OPTIONS]
+
+Report fan wear from the RPM-at-PWM history recorded by `qute monitor`.
+A worn fan needs more PWM for the same speed, and its maximum speed drops.
+
+OPTIONS:
+  -i, --index                   Fan Index. Optional, all fans by default.
+  -h, --help                    Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let index: Option<u8> = args
+        .opt_value_from_str(["-i", "--index"])
+        .with_context(|| "invalid input for index")?;
+    let config = ctx.get_config()?;
+    let fans: Vec<u8> = match index {
+        Some(idx) if idx >= config.fan.len() as u8 => {
+            return Err(anyhow!(format!("fan health: invalid fan index {}", idx)));
+        }
+        Some(idx) => vec![idx],
+        None => (0..config.fan.len() as u8).collect(),
+    };
+    let tracker = Tracker::load()?;
+    for index in fans {
+        let wear = match tracker.get(index) {
+            Some(v) if !v.buckets.is_empty() => v,
+            _ => {
+                println!("fan {}: no history, run `qute monitor` to record", index);
+                continue;
+            }
+        };
+        println!("fan {}:", index);
+        println!("  pwm      baseline  recent    change  30-day trend");
+        for bucket in wear.buckets.iter() {
+            let baseline = if bucket.has_baseline() {
+                format!("{:.0}", bucket.baseline)
+            } else {
+                String::from("-")
+            };
+            let change = if bucket.has_baseline() {
+                format!("{:+.0}%", -bucket.drop_ratio() * 100.0)
+            } else {
+                String::from("-")
+            };
+            let days = &bucket.daily[bucket.daily.len().saturating_sub(30)..];
+            let trend = match (days.first(), days.last()) {
+                (Some(first), Some(last)) if days.len() > 1 => {
+                    format!("{:+.0}%", (last.1 - first.1) / first.1 * 100.0)
+                }
+                _ => String::from("-"),
+            };
+            println!(
+                "  {:>3}-{:<3}  {:>8}  {:>8.0}  {:>6}  {:>12}",
+                bucket.pwm,
+                bucket.pwm as u16 + 15,
+                baseline,
+                bucket.recent,
+                change,
+                trend
+            );
+        }
+        let alerts = wear.alerts();
+        if alerts.is_empty() {
+            println!("√ fan {}: no sign of wear", index);
+        }
+        for alert in alerts {
+            println!("✗ {}", alert);
+        }
+    }
     Ok(())
 }
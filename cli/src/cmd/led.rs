@@ -1,9 +1,431 @@
-use crate::ctx::Context;
-use anyhow::Result;
+use crate::{
+    ctx::Context,
+    disk::bay,
+    led::{
+        pattern::Pattern,
+        rules::{self, Engine},
+        schedule::{Rule, Schedule},
+    },
+    utils,
+};
+use anyhow::{Context as _, Result};
 use pico_args::Arguments;
+use qute_ctrl::{DiskDrive, LedColor, LedControl, LedMode, SwitchState};
+use std::{path::PathBuf, time::Duration};
 
-#[allow(unused)]
-pub fn run(_args: &mut Arguments, _ctx: &Context) -> Result<()> {
-    println!("To Be Done!");
+pub fn run(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    let cmd = args.subcommand().ok().flatten().unwrap_or_default();
+    match cmd.as_str() {
+        "show" => return process_show(args, ctx),
+        "status" => return process_status(args, ctx),
+        "ident" => return process_ident(args, ctx),
+        "disk" => return process_disk(args, ctx),
+        "fan" => return process_fan(args, ctx),
+        "usb" => return process_usb(args, ctx),
+        "bbu" => return process_bbu(args, ctx),
+        "10g" => return process_10g(args, ctx),
+        "brightness" => return process_brightness(args, ctx),
+        "play" => return process_play(args, ctx),
+        "rules" => return process_rules(args, ctx),
+        "schedule" => return process_schedule(args, ctx),
+        _ => {}
+    }
+    print_help();
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        r"qute led [OPTIONS] [COMMANDS]
+LED control
+
+OPTIONS:
+  -h, --help                 Print this help text.
+
+COMMANDS:
+  show                       Show the LED configuration
+  status                     Set the status LED
+  ident                      Turn the enclosure ident LED on or off
+  disk                       Set activity, ident, present or error LED of a bay
+  fan                        Set the LED of a fan
+  usb                        Set the front USB LED
+  bbu                        Set the BBU LED
+  10g                        Turn the 10G NIC present LED on or off
+  brightness                 Set LED brightness
+  play                       Play a LED pattern
+  rules                      Reflect system conditions on LEDs
+  schedule                   Apply LED changes by time of day
+"
+    );
+}
+
+fn process_show(_args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led show [OPTIONS]
+
+Show the LED configuration kept by EC firmware. LEDs of disk bays cannot be read back.
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let chip = ctx.get_platform()?;
+    println!("{}", chip.get_led_state()?);
+    Ok(())
+}
+
+fn process_status(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led status [OPTIONS] <off|on|blink>
+
+Set the status LED
+
+OPTIONS:
+  -c, --color                green | red | auto, default green; auto alternates green and red
+                             while blinking.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let color: LedColor = args
+        .opt_value_from_str(["-c", "--color"])
+        .with_context(|| "invalid input for color")?
+        .unwrap_or(LedColor::Green);
+    let mode: LedMode = args
+        .free_from_str()
+        .with_context(|| "invalid input for mode")?;
+    let chip = ctx.get_platform()?;
+    match mode {
+        LedMode::Off => chip.set_status_led(color, false)?,
+        LedMode::On => chip.set_status_led(color, true)?,
+        LedMode::Blink => chip.blink_status_led(color, true)?,
+    }
+    println!("√ status LED was set to {} {}", color, mode);
+    Ok(())
+}
+
+fn process_ident(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led ident [OPTIONS] <on|off>
+
+Turn the enclosure ident LED on or off
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let state: SwitchState = args
+        .free_from_str()
+        .with_context(|| "invalid input for state")?;
+    let chip = ctx.get_platform()?;
+    chip.set_enclosure_ident_led(state.is_on())?;
+    println!("√ enclosure ident LED was turned {}", state);
+    Ok(())
+}
+
+fn process_disk(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led disk [OPTIONS] <active|ident|present|error> <BAY> <on|off>
+
+Set activity, ident, present or error LED of a bay, see `qute disk list` for bays
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let kind: String = args
+        .free_from_str()
+        .with_context(|| "led disk: require active, ident, present or error")?;
+    let index: u8 = args
+        .free_from_str()
+        .with_context(|| "invalid input for bay")?;
+    let state: SwitchState = args
+        .free_from_str()
+        .with_context(|| "invalid input for state")?;
+    let mut chip = ctx.get_platform()?;
+    let bay = chip
+        .bays()?
+        .into_iter()
+        .find(|v| v.index == index)
+        .ok_or_else(|| anyhow!("no bay {}", index))?;
+    let enable = state.is_on();
+    match kind.as_str() {
+        "active" => chip.set_disk_active_led(bay.port_id, enable)?,
+        "ident" => {
+            chip.set_disk_ident_led(bay.port_id, enable)?;
+            bay::record(bay.port_id, |v| v.ident = enable);
+        }
+        "present" => chip.set_present_led(bay.port_id, enable)?,
+        "error" => {
+            chip.set_disk_err_led(bay.port_id, enable)?;
+            bay::record(bay.port_id, |v| v.error = enable);
+        }
+        _ => return Err(anyhow!("led disk: invalid LED {}", kind)),
+    }
+    println!("√ {} LED of bay {} was turned {}", kind, bay.index, state);
+    Ok(())
+}
+
+fn process_fan(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led fan [OPTIONS] <green|red|auto>
+
+Set the LED of a fan; auto hands it back to EC firmware
+
+OPTIONS:
+  -i, --index                 Fan Index, default 0.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let index: u8 = args
+        .opt_value_from_str(["-i", "--index"])
+        .with_context(|| "invalid input for index")?
+        .unwrap_or(0);
+    let color: LedColor = args
+        .free_from_str()
+        .with_context(|| "invalid input for color")?;
+    let chip = ctx.get_platform()?;
+    chip.set_fan_led(index, color)?;
+    println!("√ LED of fan {} was set to {}", index, color);
+    Ok(())
+}
+
+fn process_usb(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led usb [OPTIONS] <VALUE>
+
+Set the front USB LED to the raw value of EC firmware; 0 is off
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let value: u8 = args
+        .free_from_str()
+        .with_context(|| "invalid input for value")?;
+    let chip = ctx.get_platform()?;
+    chip.set_front_usb_led(value)?;
+    println!("√ front USB LED was set to {}", value);
+    Ok(())
+}
+
+fn process_bbu(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led bbu [OPTIONS] <green|red|both> <on|off>
+
+Set the BBU LED
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let color: String = args
+        .free_from_str()
+        .with_context(|| "led bbu: require green, red or both")?;
+    let state: SwitchState = args
+        .free_from_str()
+        .with_context(|| "invalid input for state")?;
+    let arg = match color.as_str() {
+        "green" => 0,
+        "red" => 1,
+        "both" => 2,
+        _ => return Err(anyhow!("led bbu: invalid color {}", color)),
+    };
+    let chip = ctx.get_platform()?;
+    chip.set_bbu_led(arg, state.is_on() as u8)?;
+    println!("√ {} BBU LED was turned {}", color, state);
+    Ok(())
+}
+
+fn process_10g(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led 10g [OPTIONS] <on|off>
+
+Turn the 10G NIC present LED on or off
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let state: SwitchState = args
+        .free_from_str()
+        .with_context(|| "invalid input for state")?;
+    let chip = ctx.get_platform()?;
+    chip.set_10G_led(state.is_on())?;
+    println!("√ 10G NIC present LED was turned {}", state);
+    Ok(())
+}
+
+fn process_brightness(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led brightness [OPTIONS] <VALUE>
+
+Set LED brightness [0-255]
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let value: u8 = args
+        .free_from_str()
+        .with_context(|| "invalid input for brightness")?;
+    let chip = ctx.get_platform()?;
+    chip.set_led_by_pwm(value)?;
+    println!("√ LED brightness was set to {}", value);
+    Ok(())
+}
+
+fn process_play(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led play [OPTIONS] <PATTERN>
+
+Play a LED pattern until interrupted by Ctrl-C, then restore LEDs
+
+PATTERN:
+  alternate[:<ms>]           Alternating green and red, default 500 ms each
+  code:<n>[:<color>]         Blink code, n flashes then a pause, default red
+  breathe[:<ms>]             Brightness rises and falls, default period 4000 ms
+  <action>@<ms>,...          Custom steps, e.g. green@500,off@200,brightness:40@1000
+                             actions: off | green | red | blink-green | blink-red | blink-auto
+                             | brightness:<0-255>
+
+OPTIONS:
+  -n, --times                Times to play, default forever.
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let times: Option<u32> = args
+        .opt_value_from_str(["-n", "--times"])
+        .with_context(|| "invalid input for times")?;
+    let pattern: Pattern = args
+        .free_from_str()
+        .with_context(|| "led play: require a pattern")?;
+    let chip = ctx.get_platform()?;
+    utils::catch_terminate();
+    pattern.play(&chip, times)?;
+    println!("√ LEDs were restored");
+    Ok(())
+}
+
+fn process_schedule(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led schedule [OPTIONS] <RULE>...
+
+Apply LED changes by time of day until interrupted by Ctrl-C, then restore LEDs
+
+RULE:
+  <start>-<end>=<action>     e.g. 22:00-07:00=brightness:0 dims front LEDs at night; the range
+                             may wrap over midnight, later rules win.
+                             actions: off | green | red | blink-green | blink-red | blink-auto
+                             | brightness:<0-255>
+
+OPTIONS:
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let mut rules = vec![];
+    while let Some(rule) = args.opt_free_from_str::<Rule>()? {
+        rules.push(rule);
+    }
+    if rules.is_empty() {
+        return Err(anyhow!(
+            "led schedule: require a rule, e.g. 22:00-07:00=brightness:0"
+        ));
+    }
+    let chip = ctx.get_platform()?;
+    utils::catch_terminate();
+    Schedule { rules }.run(&chip)?;
+    println!("√ LEDs were restored");
+    Ok(())
+}
+
+fn process_rules(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute led rules [OPTIONS] [RULE]...
+
+Reflect system conditions on LEDs until interrupted by Ctrl-C, then restore LEDs. For each LED,
+the first rule whose condition holds wins.
+
+RULE:
+  <condition>=<action>       e.g. md-degraded=blink-red
+                             conditions: md-degraded | fan-ng | temp><℃> | link-down[:<iface>]
+                             | always
+                             actions: off | green | red | blink-green | blink-red | blink-auto
+                             | brightness:<0-255> | 10g:<on|off>
+  default: md-degraded=blink-red fan-ng=red temp>60=blink-red always=green
+
+OPTIONS:
+      --interval             Seconds between evaluations, default 5.
+      --sysfs                Root of sysfs, default /sys
+      --procfs               Root of procfs, default /proc
+  -h, --help                  Print this help text.
+"
+        );
+        return Ok(());
+    }
+    let interval: u64 = args
+        .opt_value_from_str("--interval")
+        .with_context(|| "invalid input for interval")?
+        .unwrap_or(5);
+    let sysfs: PathBuf = args
+        .opt_value_from_str("--sysfs")?
+        .unwrap_or_else(|| PathBuf::from("/sys"));
+    let procfs: PathBuf = args
+        .opt_value_from_str("--procfs")?
+        .unwrap_or_else(|| PathBuf::from("/proc"));
+    let mut rules = vec![];
+    while let Some(rule) = args.opt_free_from_str::<rules::Rule>()? {
+        rules.push(rule);
+    }
+    if rules.is_empty() {
+        rules = rules::DEFAULT_RULES
+            .iter()
+            .map(|v| v.parse())
+            .collect::<Result<Vec<_>>>()?;
+    }
+    let engine = Engine {
+        rules,
+        fans: ctx.get_config()?.fan.len() as u8,
+        interval: Duration::from_secs(interval),
+        procfs,
+        sysfs,
+    };
+    let chip = ctx.get_platform()?;
+    utils::catch_terminate();
+    engine.run(&chip)?;
+    println!("√ LEDs were restored");
     Ok(())
 }
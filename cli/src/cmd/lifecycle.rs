@@ -0,0 +1,85 @@
+use crate::{ctx::Context, fan};
+use anyhow::Result;
+use pico_args::Arguments;
+use qute_ctrl::{LedColor, LedControl};
+
+pub fn run(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    let cmd = args.subcommand().ok().flatten().unwrap_or_default();
+    if ctx.get_opts().help || cmd.is_empty() {
+        print_help();
+        return Ok(());
+    }
+    let chip = ctx.get_platform()?;
+    match cmd.as_str() {
+        "boot" => {
+            chip.blink_status_led(LedColor::Green, true)?;
+            println!("√ status LED is blinking green");
+        }
+        "ready" => {
+            chip.set_status_led(LedColor::Green, true)?;
+            println!("√ status LED was set to green");
+        }
+        "shutdown" => {
+            // the fans are handed back even if the LED fails
+            let led = chip.blink_status_led(LedColor::Red, true);
+            match &led {
+                Ok(()) => println!("√ status LED is blinking red"),
+                Err(e) => warn!("failed to blink the status LED: {:#}", e),
+            }
+            // EC fw keeps the fans cooling while the host is gone
+            fan::hand_back(&chip)?;
+            led?;
+        }
+        _ => {
+            print_help();
+            return Err(anyhow!("lifecycle: invalid stage {}", cmd));
+        }
+    }
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        r"qute lifecycle [OPTIONS] [COMMANDS]
+Show boot and shutdown on the status LED, meant for systemd units
+
+OPTIONS:
+  -h, --help                 Print this help text.
+
+COMMANDS:
+  boot                       Blink the status LED green
+  ready                      Set the status LED steady green
+  shutdown                   Blink the status LED red, hand fans back to EC firmware
+
+EXAMPLE:
+  # /etc/systemd/system/qute-lifecycle.service
+  [Unit]
+  Description=Status LED of boot and shutdown
+  DefaultDependencies=no
+  After=local-fs.target
+  Before=sysinit.target shutdown.target
+  Conflicts=shutdown.target
+
+  [Service]
+  Type=oneshot
+  RemainAfterExit=yes
+  ExecStart=/usr/bin/qute lifecycle boot
+  ExecStop=/usr/bin/qute lifecycle shutdown
+
+  [Install]
+  WantedBy=sysinit.target
+
+  # /etc/systemd/system/qute-ready.service
+  [Unit]
+  Description=Status LED of boot finished
+  After=multi-user.target
+
+  [Service]
+  Type=oneshot
+  ExecStart=/usr/bin/qute lifecycle ready
+
+  [Install]
+  WantedBy=multi-user.target
+"
+    );
+}
@@ -1,6 +1,8 @@
+pub mod disk;
 pub mod eup;
 pub mod fan;
 pub mod led;
+pub mod lifecycle;
 pub mod monitor;
 pub mod power;
 pub mod temp;
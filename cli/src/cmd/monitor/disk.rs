@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use qute_ctrl::disk::{self, PowerMode};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+/// time constant of a sleeping disk cooling down to ambient
+const COOLING_TIME: Duration = Duration::from_secs(30 * 60);
+
+/// temperature read from a disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reading {
+    Temp(f32),
+    /// in standby, not read to keep it spun down
+    Asleep,
+    Unavailable,
+}
+
+/// last known temperatures of disks, reused while disks sleep
+#[derive(Default)]
+pub struct DiskTemps {
+    last: HashMap<PathBuf, (f32, Instant)>,
+}
+
+impl DiskTemps {
+    /// temperatures of physical disks, via native SMART, or via smartctl; sleeping disks are not woken
+    /// up, their last known temperatures are decayed towards ambient instead
+    pub fn read_all(
+        &mut self,
+        sysfs: &Path,
+        native: bool,
+        ambient: f32,
+    ) -> Result<Vec<(PathBuf, f32)>> {
+        let now = Instant::now();
+        let mut res = vec![];
+        for dev in disk::list_disks_in(sysfs)?.into_iter().map(|v| v.dev) {
+            let reading = if native {
+                read_native(&dev)
+            } else {
+                read_smartctl(&dev)?
+            };
+            if let Some(temp) = self.update(&dev, reading, ambient, now) {
+                res.push((dev, temp));
+            }
+        }
+        Ok(res)
+    }
+
+    fn update(&mut self, dev: &Path, reading: Reading, ambient: f32, now: Instant) -> Option<f32> {
+        match reading {
+            Reading::Temp(temp) => {
+                self.last.insert(dev.to_owned(), (temp, now));
+                Some(temp)
+            }
+            Reading::Asleep => match self.last.get(dev) {
+                Some(&(temp, at)) => {
+                    let value = decay(temp, ambient, now.duration_since(at));
+                    info!(
+                        "{}: in standby, skipped, {:.1} ℃ at last read, about {:.1} ℃ now",
+                        dev.display(),
+                        temp,
+                        value
+                    );
+                    Some(value)
+                }
+                None => {
+                    info!("{}: in standby, skipped", dev.display());
+                    None
+                }
+            },
+            Reading::Unavailable => {
+                warn!("{}: no temperature available", dev.display());
+                None
+            }
+        }
+    }
+}
+
+/// temperature of a body cooling from `temp` towards `ambient` over `elapsed`
+fn decay(temp: f32, ambient: f32, elapsed: Duration) -> f32 {
+    if temp <= ambient {
+        return temp;
+    }
+    let ratio = (-elapsed.as_secs_f32() / COOLING_TIME.as_secs_f32()).exp();
+    ambient + (temp - ambient) * ratio
+}
+
+/// via native SMART; disks that do not answer, e.g. behind a USB bridge, are read via smartctl if
+/// installed
+fn read_native(dev: &Path) -> Reading {
+    let res = disk::check_power_mode(dev).and_then(|mode| {
+        if mode == PowerMode::Standby {
+            return Ok(Reading::Asleep);
+        }
+        let temp = disk::read_temperature(dev)?;
+        Ok(temp.map(Reading::Temp).unwrap_or(Reading::Unavailable))
+    });
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            trace!(
+                "{}: native SMART failed: {}, try smartctl",
+                dev.display(),
+                e
+            );
+            read_smartctl(dev).unwrap_or(Reading::Unavailable)
+        }
+    }
+}
+
+fn read_smartctl(dev: &Path) -> Result<Reading> {
+    let output = Command::new("smartctl")
+        .args(["-n", "standby", "-A", "-j"])
+        .arg(dev.display().to_string())
+        .output()
+        .with_context(|| "require smartctl to be installed")?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let obj = json::parse(&text).with_context(|| "invalid output of smartctl")?;
+    if let Some(temp) = obj["temperature"]["current"].as_f32() {
+        return Ok(Reading::Temp(temp));
+    }
+    // with -n standby, smartctl exits without reading a sleeping disk
+    let asleep = obj["smartctl"]["messages"].members().any(|v| {
+        let text = v["string"].as_str().unwrap_or_default();
+        text.contains("STANDBY") || text.contains("SLEEP")
+    });
+    if asleep {
+        Ok(Reading::Asleep)
+    } else {
+        Ok(Reading::Unavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decay_towards_ambient() {
+        assert_eq!(decay(40.0, 25.0, Duration::from_secs(0)), 40.0);
+        let value = decay(40.0, 25.0, COOLING_TIME);
+        assert!((value - 30.52).abs() < 0.01);
+        assert!((decay(40.0, 25.0, COOLING_TIME * 20) - 25.0).abs() < 0.01);
+        // colder than ambient already
+        assert_eq!(decay(20.0, 25.0, COOLING_TIME), 20.0);
+    }
+
+    #[test]
+    fn reuse_last_temperature_while_asleep() {
+        let mut temps = DiskTemps::default();
+        let dev = Path::new("/dev/sda");
+        let now = Instant::now();
+        assert_eq!(temps.update(dev, Reading::Asleep, 25.0, now), None);
+        assert_eq!(
+            temps.update(dev, Reading::Temp(38.0), 25.0, now),
+            Some(38.0)
+        );
+        let later = now + COOLING_TIME;
+        let value = temps.update(dev, Reading::Asleep, 25.0, later).unwrap();
+        assert!(value > 25.0 && value < 38.0);
+        assert_eq!(temps.update(dev, Reading::Unavailable, 25.0, later), None);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    config::{ZoneConfig, ZoneSourceConfig},
+    ctx::Context as PlatformContext,
+    disk::health::{self as disk_health, DiskHealth, ErrorLeds},
+    fan::{
+        self,
+        calibration::{self, Calibration},
+        curve::Curve,
+        health::{Evaluator, FanHealth},
+        wear::Tracker,
+    },
+    utils,
+};
+use anyhow::Result;
+use chrono::prelude::*;
+use pico_args::Arguments;
+use qute_ctrl::{platform::Platform, FanControl};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+mod disk;
+mod source;
+mod zone;
+
+use zone::Zone;
+
+/// cycles between saving fan wear history, about 10 minutes at the default interval
+const WEAR_SAVE_CYCLES: u32 = 120;
+
+/// cycles between checking SMART health of disks, about an hour at the default interval
+const DISK_HEALTH_CYCLES: u32 = 720;
+
+pub fn run(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
+    if ctx.get_opts().help {
+        print_help();
+        return Ok(());
+    }
+    let config = ctx.get_config()?;
+    let defaults = &config.monitor;
+    let mut zones = config.zones.clone();
+    apply_options(args, &mut zones)?;
+    let interval = args
+        .opt_value_from_str("--interval")?
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.interval);
+    let sysfs: PathBuf = args
+        .opt_value_from_str("--sysfs")?
+        .unwrap_or_else(|| defaults.sysfs.clone());
+    let cals = calibration::load_all()?;
+    let zones = zones
+        .iter()
+        .map(|v| Zone::new(v, &sysfs, &config.fan, &cals))
+        .collect::<Result<Vec<_>>>()?;
+    let fans = config.fan.len() as u8;
+    let chip = ctx.get_platform()?;
+    utils::catch_terminate();
+    let res = run_forever(&chip, fans, zones, cals, interval);
+    // an error of the loop takes precedence over one of handing back
+    let restored = fan::hand_back(&chip);
+    res.and(restored)
+}
+
+/// options take precedence over the config file, in every zone
+fn apply_options(args: &mut Arguments, zones: &mut [ZoneConfig]) -> Result<()> {
+    let min_temp: Option<f32> = args.opt_value_from_str("--min")?;
+    let max_temp: Option<f32> = args.opt_value_from_str("--max")?;
+    let method: Option<Method> = args.opt_value_from_str("--method")?;
+    let hysteresis: Option<f32> = args.opt_value_from_str("--hysteresis")?;
+    let ramp_up: Option<f32> = args.opt_value_from_str("--ramp-up")?;
+    let ramp_down: Option<f32> = args.opt_value_from_str("--ramp-down")?;
+    let dwell: Option<u64> = args.opt_value_from_str("--dwell")?;
+    let zero_below: Option<f32> = args.opt_value_from_str("--zero-below")?;
+    let setpoint: Option<f32> = args.opt_value_from_str("--setpoint")?;
+    let kp: Option<f32> = args.opt_value_from_str("--kp")?;
+    let ki: Option<f32> = args.opt_value_from_str("--ki")?;
+    let kd: Option<f32> = args.opt_value_from_str("--kd")?;
+    let specs: Vec<String> = args.values_from_str("--source")?;
+    for zone in zones.iter_mut() {
+        zone.min_temp = min_temp.unwrap_or(zone.min_temp);
+        zone.max_temp = max_temp.unwrap_or(zone.max_temp);
+        if zone.min_temp >= zone.max_temp {
+            return Err(anyhow!(
+                "zone {}: min temperature {} must be less than max temperature {}",
+                zone.name,
+                zone.min_temp,
+                zone.max_temp
+            ));
+        }
+        if let Some(v) = &method {
+            zone.method = v.clone();
+        }
+        let s = &mut zone.smoothing;
+        s.hysteresis = hysteresis.unwrap_or(s.hysteresis);
+        s.ramp_up = ramp_up.or(s.ramp_up);
+        s.ramp_down = ramp_down.or(s.ramp_down);
+        s.dwell = dwell.map(Duration::from_secs).unwrap_or(s.dwell);
+        s.zero_below = zero_below.or(s.zero_below);
+        let pid = &mut zone.pid;
+        pid.setpoint = setpoint.unwrap_or(pid.setpoint);
+        pid.kp = kp.unwrap_or(pid.kp);
+        pid.ki = ki.unwrap_or(pid.ki);
+        pid.kd = kd.unwrap_or(pid.kd);
+        if !specs.is_empty() {
+            zone.sources = specs.iter().cloned().map(ZoneSourceConfig::new).collect();
+        }
+    }
+    Ok(())
+}
+
+fn run_forever(
+    chip: &Platform,
+    fans: u8,
+    mut zones: Vec<Zone>,
+    cals: Vec<Calibration>,
+    interval: Duration,
+) -> Result<()> {
+    let mut evaluator = Evaluator::new(cals.clone());
+    let mut tracker = Tracker::load()?;
+    let mut error_leds = ErrorLeds::default();
+    let mut failed = vec![];
+    let mut last_pwm: HashMap<u8, u8> = HashMap::new();
+    // cycles left until the next disk check and wear save
+    let mut disk_due: u32 = 0;
+    let mut wear_due = WEAR_SAVE_CYCLES;
+    while !utils::is_terminated() {
+        let dt = Local::now();
+        let mut health = vec![];
+        for fan in 0..fans {
+            health.push((fan, evaluator.evaluate(chip, fan)?));
+            if let Some((pwm, rpm)) = evaluator.reading(fan) {
+                for alert in tracker.record(fan, pwm, rpm) {
+                    warn!("{}, consider replacing the fan", alert);
+                }
+            }
+        }
+        if disk_due == 0 {
+            check_disks(chip, &mut error_leds);
+            disk_due = DISK_HEALTH_CYCLES;
+        }
+        disk_due -= 1;
+        wear_due -= 1;
+        if wear_due == 0 {
+            tracker.save()?;
+            wear_due = WEAR_SAVE_CYCLES;
+        }
+        let cur_failed: Vec<u8> = health
+            .iter()
+            .filter(|(_, v)| v.is_failed())
+            .map(|&(fan, _)| fan)
+            .collect();
+        for &(fan, v) in health.iter() {
+            if v.is_failed() && !failed.contains(&fan) {
+                error!("fan {} {}! remaining fans are raised to maximum", fan, v);
+            } else if v == FanHealth::Degraded {
+                warn!("fan {} {}", fan, v);
+            }
+        }
+        if !cur_failed.is_empty() {
+            // keep the system cool with remaining fans
+            for fan in (0..fans).filter(|v| !cur_failed.contains(v)) {
+                chip.set_fan_speed(fan, 0xff)?;
+            }
+            println!(
+                "{}\t✗ fan {:?} failed, PWM of remaining fans was set to 255",
+                dt.format("%Y-%m-%d %H:%M:%S"),
+                cur_failed
+            );
+            failed = cur_failed;
+            last_pwm.clear();
+            zones.iter_mut().for_each(|v| v.reset());
+            sleep(interval);
+            continue;
+        }
+        if !failed.is_empty() {
+            warn!("fan {:?} recovered", failed);
+            failed.clear();
+        }
+
+        let now = Instant::now();
+        let mut changed = false;
+        for zone in zones.iter_mut() {
+            let pwm = zone.next(chip, now)?;
+            for &fan in zone.fans.iter() {
+                let cal = cals.iter().find(|v| v.fan == fan);
+                let pwm = cal.map(|v| v.floor(pwm)).unwrap_or(pwm);
+                if last_pwm.insert(fan, pwm) == Some(pwm) {
+                    continue;
+                }
+                changed = true;
+                calibration::set_pwm(chip, fan, pwm, cal)?;
+                println!(
+                    "{}\t√ PWM of fan {} ({}) was set to {}",
+                    dt.format("%Y-%m-%d %H:%M:%S"),
+                    fan,
+                    zone.name,
+                    pwm
+                );
+            }
+        }
+        if !changed {
+            println!("{}\tPWM unchanged", dt.format("%Y-%m-%d %H:%M:%S"));
+        }
+
+        sleep(interval);
+    }
+    tracker.save()
+}
+
+/// how a zone works out PWM from temperatures
+#[derive(Debug, Clone, PartialEq)]
+pub enum Method {
+    /// open loop, smoothed by `Governor`
+    Map(Mapping),
+    /// closed loop holding the setpoint temperature, see `Pid`
+    Pid,
+}
+
+/// open loop mapping of temperatures to PWM
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mapping {
+    Linear,
+    Eager,
+    Step,
+    /// user defined points, min and max temperatures are not used
+    Curve(Curve),
+}
+
+impl Mapping {
+    fn apply(&self, min_temp: f32, max_temp: f32, cur_temp: f32) -> u8 {
+        let clamped = cur_temp.min(max_temp).max(min_temp);
+        let ratio = (clamped - min_temp) / (max_temp - min_temp);
+        let ratio = match self {
+            Mapping::Linear => ratio,
+            Mapping::Eager => 1.0 - (1.0 - ratio).powi(2),
+            Mapping::Step => {
+                let ratio = (ratio * 10.0).floor() / 9.0;
+                ratio.min(1.0)
+            }
+            Mapping::Curve(curve) => return curve.pwm(cur_temp),
+        };
+        (255.0 * ratio) as u8
+    }
+}
+
+impl FromStr for Method {
+    type Err = anyhow::Error;
+    fn from_str(src: &str) -> std::result::Result<Self, Self::Err> {
+        let src = src.to_lowercase();
+        if src == "linear" {
+            return Ok(Method::Map(Mapping::Linear));
+        }
+        if src == "eager" {
+            return Ok(Method::Map(Mapping::Eager));
+        }
+        if src == "step" {
+            return Ok(Method::Map(Mapping::Step));
+        }
+        if src == "pid" {
+            return Ok(Method::Pid);
+        }
+        if let Some(points) = src.strip_prefix("curve:") {
+            return Ok(Method::Map(Mapping::Curve(points.parse()?)));
+        }
+        Err(anyhow!("invalid method"))
+    }
+}
+
+impl Default for Method {
+    fn default() -> Self {
+        Method::Map(Mapping::Linear)
+    }
+}
+
+/// check SMART health of disks in bays, lighting error LEDs of failing ones
+fn check_disks(chip: &Platform, leds: &mut ErrorLeds) {
+    let reports = match disk_health::check_bays(chip, leds) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("failed to check disk health: {:#}", e);
+            return;
+        }
+    };
+    for (bay, disk, report) in reports {
+        match report {
+            Ok(Some(report)) if report.health == DiskHealth::Failing => error!(
+                "{} in bay {} failing: {}",
+                disk.dev.display(),
+                bay.index,
+                report.reasons.join(", ")
+            ),
+            Ok(Some(report)) if report.health == DiskHealth::Warning => warn!(
+                "{} in bay {}: {}",
+                disk.dev.display(),
+                bay.index,
+                report.reasons.join(", ")
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("{}: failed to read SMART: {:#}", disk.dev.display(), e),
+        }
+    }
+}
+
+fn print_help() {
+    println!(
+        r"qute monitor [OPTIONS]
+
+automatically adjust fan speed based on cpu and hdd temperatures.
+
+OPTIONS:
+  -h, --help                   Print this help text.
+  --min                         Minimal temperature, default 5 ℃
+  --max                        Maximum temperature, default 50 ℃
+  --interval                  Seconds between adjustments, default 5
+  --source                    Temperature source, repeatable; default ec:cpu0 and disk
+                                    - ec:<sensor>: EC sensor, e.g. ec:cpu0, see `qute temp all`
+                                    - hwmon[:<name>]: Linux hwmon, e.g. hwmon:coretemp, hwmon:drivetemp
+                                    - nvme: NVMe drives, via hwmon
+                                    - thermal[:<type>]: Linux thermal zones, e.g. thermal:x86_pkg_temp
+                                    - disk: disks, read SMART natively, smartctl as fallback
+                                    - smartctl: disks, requires smartctl to be installed
+                                    - cmd:<command>: a command printing temperatures, one per line
+  --sysfs                      Root of sysfs, default /sys
+  --method                  Available options: Linear | Eager | Step | curve:<points> | pid
+                                    - Linear: linearly adjust pwm based on temperatures
+                                    - Eager:  higher temperatures, faster fan speed
+                                    - Step: level based
+                                    - curve:<temp>:<pwm>,...: interpolate between points, e.g.
+                                      curve:30:60,45:150,55:255; --min and --max are not used
+                                    - pid: hold the hottest temperature at the setpoint; PWM is kept
+                                      within min_pwm-max_pwm of the fans in the config file, and
+                                      above the stall floors of calibrated fans; the options
+                                      from --hysteresis to --zero-below do not apply
+  --hysteresis              ℃ the temperature must fall before PWM is lowered, default 0
+  --ramp-up                 Max PWM rise per second, default unlimited
+  --ramp-down             Max PWM fall per second, default unlimited
+  --dwell                     Seconds a PWM is held before it is lowered, default 0
+  --zero-below            Stop the fan below this temperature, until it is hysteresis warmer
+  --setpoint                Temperature pid holds, default 40 ℃
+  --kp                         PWM per ℃ above the setpoint, default 10
+  --ki                          PWM per ℃·s above the setpoint, default 0.5
+  --kd                         PWM per ℃/s of temperature rise, default 0
+
+SMART health of disks in bays is checked hourly; error LEDs of failing disks are lit.
+Disks in standby are not woken up; their last temperatures, cooling towards ambient, are used
+instead. Run with -v 2 to see the skipped disks.
+
+Fans are driven in zones of the config file, each by its own sources; without zones, all fans
+of the config file are driven as one zone. A zone takes its sources, curve and smoothing from
+its first fan and the rest from the monitor section, unless set in the zone. Sources of a zone
+may have an offset, a weight scaling the PWM they ask for, and a curve of their own; the
+highest PWM asked for wins. Options given take precedence in every zone.
+
+Fans are handed back to EC firmware (auto mode) when monitor exits.
+"
+    );
+}
@@ -0,0 +1,345 @@
+use super::disk::DiskTemps;
+use anyhow::{Context, Result};
+use qute_ctrl::{platform::Platform, SensorId, Temperature};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// a source of temperatures
+pub trait TemperatureSource {
+    /// name of source, for logging
+    fn name(&self) -> String;
+
+    /// read temperatures in ℃, labeled
+    fn read(&self, chip: &Platform) -> Result<Vec<(String, f32)>>;
+}
+
+/// parse source spec:
+/// - `ec:<sensor>`: EC sensor, e.g. `ec:cpu0`
+/// - `hwmon[:<name>]`: Linux hwmon, e.g. `hwmon:coretemp`, `hwmon:drivetemp`
+/// - `nvme`: NVMe drives, via hwmon
+/// - `thermal[:<type>]`: Linux thermal zones, e.g. `thermal:x86_pkg_temp`
+/// - `disk`: disks, via native SMART
+/// - `smartctl`: disks, via smartctl
+/// - `cmd:<command>`: a command printing temperatures in ℃, one per line
+pub fn parse(spec: &str, sysfs: &Path) -> Result<Box<dyn TemperatureSource>> {
+    let mut parts = spec.trim().splitn(2, ':');
+    let kind = parts.next().unwrap_or_default().to_lowercase();
+    let arg = parts.next().map(|v| v.trim().to_owned());
+    let source: Box<dyn TemperatureSource> = match (kind.as_str(), arg) {
+        ("ec", Some(sensor)) => Box::new(EcSource {
+            sensor: sensor.parse()?,
+        }),
+        ("hwmon", name) => Box::new(HwmonSource {
+            sysfs: sysfs.to_owned(),
+            name,
+        }),
+        ("nvme", None) => Box::new(HwmonSource {
+            sysfs: sysfs.to_owned(),
+            name: Some(String::from("nvme")),
+        }),
+        ("thermal", zone) => Box::new(ThermalSource {
+            sysfs: sysfs.to_owned(),
+            zone,
+        }),
+        ("disk", None) => Box::new(DiskSource::new(sysfs, true)),
+        ("smartctl", None) => Box::new(DiskSource::new(sysfs, false)),
+        ("cmd", Some(cmd)) if !cmd.is_empty() => Box::new(CommandSource { cmd }),
+        _ => return Err(anyhow!("invalid temperature source: {}", spec)),
+    };
+    Ok(source)
+}
+
+pub struct EcSource {
+    pub sensor: SensorId,
+}
+
+impl TemperatureSource for EcSource {
+    fn name(&self) -> String {
+        format!("ec:{}", self.sensor)
+    }
+
+    fn read(&self, chip: &Platform) -> Result<Vec<(String, f32)>> {
+        let res = chip
+            .get_sensor_temperature(self.sensor)?
+            .map(|v| vec![(self.sensor.to_string(), v)])
+            .unwrap_or_default();
+        Ok(res)
+    }
+}
+
+/// `<sysfs>/class/hwmon/hwmon*/temp*_input`, which includes coretemp, drivetemp and nvme
+pub struct HwmonSource {
+    pub sysfs: PathBuf,
+    /// driver name, all if None
+    pub name: Option<String>,
+}
+
+impl TemperatureSource for HwmonSource {
+    fn name(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("hwmon:{}", name),
+            None => String::from("hwmon"),
+        }
+    }
+
+    fn read(&self, _chip: &Platform) -> Result<Vec<(String, f32)>> {
+        read_hwmon(&self.sysfs, self.name.as_deref())
+    }
+}
+
+/// `<sysfs>/class/thermal/thermal_zone*/temp`
+pub struct ThermalSource {
+    pub sysfs: PathBuf,
+    /// zone type, all if None
+    pub zone: Option<String>,
+}
+
+impl TemperatureSource for ThermalSource {
+    fn name(&self) -> String {
+        match self.zone {
+            Some(ref zone) => format!("thermal:{}", zone),
+            None => String::from("thermal"),
+        }
+    }
+
+    fn read(&self, _chip: &Platform) -> Result<Vec<(String, f32)>> {
+        read_thermal(&self.sysfs, self.zone.as_deref())
+    }
+}
+
+/// EC sensor taken as ambient for disks; the system temperature inside the chassis
+const AMBIENT_SENSOR: &str = "sys0";
+
+/// ambient if the EC sensor is not available, in ℃
+const DEFAULT_AMBIENT: f32 = 25.0;
+
+/// disks, via native SMART or smartctl; sleeping disks are not woken up
+pub struct DiskSource {
+    pub sysfs: PathBuf,
+    /// false to read via smartctl
+    pub native: bool,
+    temps: RefCell<DiskTemps>,
+}
+
+impl DiskSource {
+    pub fn new(sysfs: &Path, native: bool) -> Self {
+        Self {
+            sysfs: sysfs.to_owned(),
+            native,
+            temps: RefCell::new(DiskTemps::default()),
+        }
+    }
+}
+
+impl TemperatureSource for DiskSource {
+    fn name(&self) -> String {
+        match self.native {
+            true => String::from("disk"),
+            false => String::from("smartctl"),
+        }
+    }
+
+    fn read(&self, chip: &Platform) -> Result<Vec<(String, f32)>> {
+        let ambient = AMBIENT_SENSOR
+            .parse()
+            .ok()
+            .and_then(|sensor| chip.get_sensor_temperature(sensor).ok().flatten())
+            .unwrap_or(DEFAULT_AMBIENT);
+        let res = self
+            .temps
+            .borrow_mut()
+            .read_all(&self.sysfs, self.native, ambient)?
+            .into_iter()
+            .map(|(dev, temp)| (dev.display().to_string(), temp))
+            .collect();
+        Ok(res)
+    }
+}
+
+/// a command printing temperatures in ℃, one per line
+pub struct CommandSource {
+    pub cmd: String,
+}
+
+impl TemperatureSource for CommandSource {
+    fn name(&self) -> String {
+        format!("cmd:{}", self.cmd)
+    }
+
+    fn read(&self, _chip: &Platform) -> Result<Vec<(String, f32)>> {
+        run_command(&self.cmd)
+    }
+}
+
+/// temperatures of hwmon devices with the driver name, all if None
+fn read_hwmon(sysfs: &Path, name: Option<&str>) -> Result<Vec<(String, f32)>> {
+    let mut res = vec![];
+    for dir in list_dir(&sysfs.join("class/hwmon"), "hwmon")? {
+        let driver = read_trimmed(&dir.join("name")).unwrap_or_default();
+        if name.map(|v| v != driver).unwrap_or(false) {
+            continue;
+        }
+        let hwmon = dir.file_name().unwrap().to_string_lossy().into_owned();
+        for input in list_dir(&dir, "temp")? {
+            let file = input.file_name().unwrap().to_string_lossy().into_owned();
+            if !file.ends_with("_input") {
+                continue;
+            }
+            let value = match read_millidegree(&input) {
+                Some(v) => v,
+                None => continue,
+            };
+            let label = dir.join(file.replace("_input", "_label"));
+            let label = match read_trimmed(&label) {
+                Some(label) => format!("{}/{}", driver, label),
+                None => format!("{}/{}", driver, hwmon),
+            };
+            res.push((label, value));
+        }
+    }
+    Ok(res)
+}
+
+/// temperatures of thermal zones with the type, all if None
+fn read_thermal(sysfs: &Path, zone: Option<&str>) -> Result<Vec<(String, f32)>> {
+    let mut res = vec![];
+    for dir in list_dir(&sysfs.join("class/thermal"), "thermal_zone")? {
+        let kind = read_trimmed(&dir.join("type")).unwrap_or_default();
+        if zone.map(|v| v != kind).unwrap_or(false) {
+            continue;
+        }
+        if let Some(value) = read_millidegree(&dir.join("temp")) {
+            res.push((kind, value));
+        }
+    }
+    Ok(res)
+}
+
+fn run_command(cmd: &str) -> Result<Vec<(String, f32)>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .output()
+        .with_context(|| format!("failed to run {}", cmd))?;
+    if !output.status.success() {
+        return Err(anyhow!("{} exited with {}", cmd, output.status));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let res = text
+        .lines()
+        .filter_map(|line| line.trim().parse::<f32>().ok())
+        .map(|v| (cmd.to_owned(), v))
+        .collect();
+    Ok(res)
+}
+
+/// entries of dir with the prefix, sorted; empty if dir not exists
+fn list_dir(dir: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut res = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            res.push(entry.path());
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|v| v.trim().to_owned())
+}
+
+/// sysfs temperatures are in millidegree Celsius
+fn read_millidegree(path: &Path) -> Option<f32> {
+    let value: i64 = read_trimmed(path)?.parse().ok()?;
+    Some(value as f32 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qute_ctrl::testutil::TempDir;
+
+    /// fake sysfs tree
+    fn sysfs(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("source-{}", name));
+        root.write("class/hwmon/hwmon0/name", "coretemp\n");
+        root.write("class/hwmon/hwmon0/temp1_input", "45000\n");
+        root.write("class/hwmon/hwmon0/temp1_label", "Package id 0\n");
+        root.write("class/hwmon/hwmon0/temp2_input", "43000\n");
+        root.write("class/hwmon/hwmon0/temp2_label", "Core 0\n");
+        root.write("class/hwmon/hwmon1/name", "drivetemp\n");
+        root.write("class/hwmon/hwmon1/temp1_input", "38000\n");
+        root.write("class/hwmon/hwmon2/name", "nvme\n");
+        root.write("class/hwmon/hwmon2/temp1_input", "51850\n");
+        root.write("class/hwmon/hwmon2/temp1_label", "Composite\n");
+        root.write("class/thermal/thermal_zone0/type", "acpitz\n");
+        root.write("class/thermal/thermal_zone0/temp", "27800\n");
+        root.write("class/thermal/thermal_zone1/type", "x86_pkg_temp\n");
+        root.write("class/thermal/thermal_zone1/temp", "46000\n");
+        root
+    }
+
+    #[test]
+    fn parse_spec() {
+        let root = Path::new("/sys");
+        assert_eq!(parse("ec:cpu0", root).unwrap().name(), "ec:cpu0");
+        assert_eq!(parse("hwmon", root).unwrap().name(), "hwmon");
+        assert_eq!(parse("nvme", root).unwrap().name(), "hwmon:nvme");
+        assert_eq!(
+            parse("thermal:acpitz", root).unwrap().name(),
+            "thermal:acpitz"
+        );
+        assert_eq!(parse("disk", root).unwrap().name(), "disk");
+        assert_eq!(parse("smartctl", root).unwrap().name(), "smartctl");
+        assert_eq!(parse("cmd:echo 40", root).unwrap().name(), "cmd:echo 40");
+        assert!(parse("ec", root).is_err());
+        assert!(parse("ec:gpu0", root).is_err());
+        assert!(parse("cmd:", root).is_err());
+        assert!(parse("ipmi", root).is_err());
+    }
+
+    #[test]
+    fn hwmon() {
+        let root = sysfs("hwmon");
+        let res = read_hwmon(&root, None).unwrap();
+        assert_eq!(res.len(), 4);
+        assert_eq!(res[0], (String::from("coretemp/Package id 0"), 45.0));
+        assert_eq!(res[2], (String::from("drivetemp/hwmon1"), 38.0));
+        let res = read_hwmon(&root, Some("nvme")).unwrap();
+        assert_eq!(res, vec![(String::from("nvme/Composite"), 51.85)]);
+        assert!(read_hwmon(&root, Some("k10temp")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn thermal() {
+        let root = sysfs("thermal");
+        let res = read_thermal(&root, None).unwrap();
+        assert_eq!(res.len(), 2);
+        let res = read_thermal(&root, Some("x86_pkg_temp")).unwrap();
+        assert_eq!(res, vec![(String::from("x86_pkg_temp"), 46.0)]);
+    }
+
+    #[test]
+    fn missing_sysfs() {
+        let root = Path::new("/nonexistent");
+        assert!(read_hwmon(root, None).unwrap().is_empty());
+        assert!(read_thermal(root, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn command() {
+        let res = run_command("echo 41; echo n/a; echo 39.5").unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[1].1, 39.5);
+        assert!(run_command("exit 1").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use super::{source, source::TemperatureSource, Method};
+use crate::{
+    config::{FanConfig, ZoneConfig, ZoneSourceConfig},
+    fan::{calibration::Calibration, curve::Governor, pid::Pid},
+};
+use anyhow::Result;
+use qute_ctrl::platform::Platform;
+use std::{path::Path, time::Instant};
+
+/// works out the PWM of a zone from its temperatures
+enum Control {
+    /// open loop mapping of temperatures, smoothed
+    Map(Box<dyn Fn(f32) -> u8>, Governor),
+    /// closed loop over the hottest temperature
+    Pid(Pid),
+}
+
+/// fans driven by the same temperatures
+pub struct Zone {
+    pub name: String,
+    pub fans: Vec<u8>,
+    sources: Vec<(Box<dyn TemperatureSource>, ZoneSourceConfig)>,
+    control: Control,
+}
+
+impl Zone {
+    pub fn new(
+        config: &ZoneConfig,
+        sysfs: &Path,
+        fans: &[FanConfig],
+        cals: &[Calibration],
+    ) -> Result<Self> {
+        let sources = config
+            .sources
+            .iter()
+            .map(|v| Ok((source::parse(&v.spec, sysfs)?, v.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        let control = match &config.method {
+            Method::Pid => {
+                // the narrowest range of the fans, above stall floors of calibrated ones
+                let mut min_pwm = 0;
+                let mut max_pwm = 0xff;
+                for &fan in config.fans.iter() {
+                    if let Some(v) = fans.get(fan as usize) {
+                        min_pwm = min_pwm.max(v.min_pwm);
+                        max_pwm = max_pwm.min(v.max_pwm);
+                    }
+                    if let Some(v) = cals.iter().find(|v| v.fan == fan) {
+                        min_pwm = min_pwm.max(v.sustain_pwm);
+                    }
+                }
+                Control::Pid(Pid::new(config.pid.clone(), min_pwm.min(max_pwm), max_pwm))
+            }
+            Method::Map(mapping) => {
+                let mapping = mapping.clone();
+                let (min_temp, max_temp) = (config.min_temp, config.max_temp);
+                Control::Map(
+                    Box::new(move |temp| mapping.apply(min_temp, max_temp, temp)),
+                    Governor::new(config.smoothing.clone()),
+                )
+            }
+        };
+        Ok(Self {
+            name: config.name.clone(),
+            fans: config.fans.clone(),
+            sources,
+            control,
+        })
+    }
+
+    /// hottest temperature of each source, offset applied; (source index, temperature)
+    fn read(&self, chip: &Platform) -> Result<Vec<(usize, f32)>> {
+        let mut res = vec![];
+        for (i, (source, config)) in self.sources.iter().enumerate() {
+            match source.read(chip) {
+                Ok(temps) => {
+                    let mut max: Option<f32> = None;
+                    for (label, temp) in temps {
+                        trace!("{} {}: {} ℃", source.name(), label, temp);
+                        max = Some(max.map_or(temp, |v| v.max(temp)));
+                    }
+                    if let Some(temp) = max {
+                        res.push((i, temp + config.offset));
+                    }
+                }
+                Err(e) => warn!("temperature source {}: {:#}", source.name(), e),
+            }
+        }
+        if res.is_empty() {
+            return Err(anyhow!("zone {}: no temperature available", self.name));
+        }
+        Ok(res)
+    }
+
+    /// next PWM of the fans
+    pub fn next(&mut self, chip: &Platform, now: Instant) -> Result<u8> {
+        let temps = self.read(chip)?;
+        let hottest = temps.iter().map(|&(_, v)| v).fold(f32::MIN, f32::max);
+        trace!("zone {}: max temperature: {} ℃", self.name, hottest);
+        let sources = &self.sources;
+        let temps: Vec<_> = temps.into_iter().map(|(i, v)| (v, &sources[i].1)).collect();
+        let pwm = match &mut self.control {
+            Control::Pid(pid) => pid.next(hottest, now),
+            Control::Map(map, governor) => {
+                // others follow the hottest, so that hysteresis applies to all
+                let shifted = |temp| demand(&temps, &**map, temp - hottest);
+                governor.next(shifted, hottest, now)
+            }
+        };
+        Ok(pwm)
+    }
+
+    /// start over, e.g. after PWM was set by others
+    pub fn reset(&mut self) {
+        match &mut self.control {
+            Control::Map(_, governor) => governor.reset(),
+            Control::Pid(pid) => pid.reset(),
+        }
+    }
+}
+
+/// PWM asked for by sources at (temperature, config), all `shift` warmer; the highest wins
+fn demand(temps: &[(f32, &ZoneSourceConfig)], map: &dyn Fn(f32) -> u8, shift: f32) -> u8 {
+    temps
+        .iter()
+        .map(|&(temp, config)| {
+            let temp = temp + shift;
+            let pwm = match &config.curve {
+                Some(curve) => curve.pwm(temp),
+                None => map(temp),
+            };
+            (pwm as f32 * config.weight).min(255.0) as u8
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demand_of_sources() {
+        let disk = ZoneSourceConfig::new(String::from("disk"));
+        let cpu = ZoneSourceConfig {
+            curve: Some("40:60,60:255".parse().unwrap()),
+            weight: 0.8,
+            ..ZoneSourceConfig::new(String::from("ec:cpu0"))
+        };
+        // 5 PWM per ℃ from 30 ℃
+        let map = |temp: f32| ((temp - 30.0).max(0.0) * 5.0).min(255.0) as u8;
+        let temps = [(45.0, &disk), (50.0, &cpu)];
+        // cpu: 158 × 0.8, disk: 75
+        assert_eq!(demand(&temps, &map, 0.0), 126);
+        let temps = [(60.0, &disk), (50.0, &cpu)];
+        assert_eq!(demand(&temps, &map, 0.0), 150);
+        // hysteresis looks at every source warmer
+        assert_eq!(demand(&temps, &map, 2.0), 160);
+        assert_eq!(demand(&[], &map, 0.0), 0);
+    }
+}
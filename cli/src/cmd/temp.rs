@@ -1,6 +1,6 @@
-use crate::{ctx::Context, utils::temp_c2f};
-use anyhow::Result;
-use qute_ctrl::Temperature;
+use crate::{ctx::Context, utils};
+use anyhow::{Context as _, Result};
+use qute_ctrl::{CalibrationMode, SensorId, SwitchState, TempCalibration, Temperature};
 
 use pico_args::Arguments;
 
@@ -17,6 +17,8 @@ pub fn run(args: &mut Arguments, ctx: &Context) -> Result<()> {
     match cmd.as_str() {
         "cpu" => return process(args, ctx, 0),
         "sys" => return process(args, ctx, 5),
+        "all" => return process_all(args, ctx),
+        "calibrate" => return process_calibrate(args, ctx),
         _ => {}
     }
     print_help();
@@ -31,7 +33,92 @@ fn process(_args: &mut Arguments, ctx: &Context, index: u8) -> Result<()> {
     };
     let chip = ctx.get_platform()?;
     let val = chip.get_temperature(index)?;
-    println!("{} temperature: {:.1} ℃ / {} ℉", tag, val, temp_c2f(val));
+    println!(
+        "{} temperature: {}",
+        tag,
+        ctx.get_config()?.output.temp(val)
+    );
+    Ok(())
+}
+
+fn process_all(_args: &mut Arguments, ctx: &Context) -> Result<()> {
+    let chip = ctx.get_platform()?;
+    let config = ctx.get_config()?;
+    for sensor in chip.sensors()? {
+        if let Some(val) = chip.get_sensor_temperature(sensor)? {
+            println!(
+                "{:<8} temperature: {}",
+                config.sensor_label(sensor),
+                config.output.temp(val)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn process_calibrate(args: &mut Arguments, ctx: &Context) -> Result<()> {
+    if ctx.get_opts().help {
+        println!(
+            r"qute temp calibrate [OPTIONS] [show|set]
+
+Show or set temperature calibration of EC firmware.
+
+EC firmware has two calibration modes, primary and secondary. Each mode has an
+enable bit (bit 0 or bit 1 of register 0x2e2) and a register holding the sensor
+to calibrate (0x27d or 0x27f). QNAP does not document what the firmware does with
+them; the readings of the sensor and the fan curve of EC firmware may change.
+Note down the current state with `show` before changing it. Use AT YOUR OWN RISK!!!
+
+OPTIONS:
+  -m, --mode [primary|secondary]  Calibration mode, required by set
+  -s, --sensor                    Sensor id or label, e.g. 5 or sys0, required by set
+  -e, --enable [on|off]           Enable or disable calibration, required by set
+  -y, --yes                       Do not ask for confirmation
+  -h, --help                      Print this help text.
+
+COMMANDS:
+  show                            Show calibration state of both modes, default
+  set                             Set calibration state of a mode
+"
+        );
+        return Ok(());
+    }
+    let cmd = args.subcommand().ok().flatten().unwrap_or_default();
+    let chip = ctx.get_platform()?;
+    match cmd.as_str() {
+        "set" => {
+            let mode: CalibrationMode = args
+                .value_from_str(["-m", "--mode"])
+                .with_context(|| "invalid input for mode")?;
+            let sensor: SensorId = args
+                .value_from_str(["-s", "--sensor"])
+                .with_context(|| "invalid input for sensor")?;
+            let enable: SwitchState = args
+                .value_from_str(["-e", "--enable"])
+                .with_context(|| "invalid input for enable")?;
+            let yes = args.contains(["-y", "--yes"]);
+            let calibration = TempCalibration {
+                mode,
+                sensor,
+                enabled: enable.is_on(),
+            };
+            let current = chip.get_temperature_calibration(mode)?;
+            println!("current calibration {}", current);
+            if !yes && !utils::confirm(&format!("set calibration {}?", calibration))? {
+                println!("canceled");
+                return Ok(());
+            }
+            chip.set_temperature_calibration(calibration)?;
+            println!("√ calibration was set to {}", calibration);
+        }
+        "show" | "" => {
+            for &mode in [CalibrationMode::Primary, CalibrationMode::Secondary].iter() {
+                let calibration = chip.get_temperature_calibration(mode)?;
+                println!("calibration {}", calibration);
+            }
+        }
+        _ => return Err(anyhow!(format!("temp calibrate: invalid command {}", cmd))),
+    }
     Ok(())
 }
 
@@ -47,6 +134,8 @@ OPTIONS:
 COMMANDS:
   cpu                          Fetch cpu temperature
   sys                           Fetch sys temperature
+  all                            Fetch temperatures of all present sensors, labeled by the config file
+  calibrate                    Show or set temperature calibration of EC firmware
 "
     );
 }
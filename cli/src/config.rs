@@ -1,8 +1,902 @@
+use crate::{
+    cmd::monitor::{Mapping, Method},
+    fan::{
+        curve::{Curve, Smoothing},
+        pid::PidParams,
+    },
+};
+use anyhow::{Context, Result};
+use json::JsonValue;
+use qute_ctrl::{disk::BayWiring, platform::Options as PlatformOptions, PortBackend, SensorId};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// read if exists, unless another file is given by `--config`
+pub const DEFAULT_PATH: &str = "/etc/qute/config.json";
+
 pub struct FanConfig {
     pub min_speed: u16,
     pub max_speed: u16,
+    /// PWM range the pid method of `qute monitor` keeps to
+    pub min_pwm: u8,
+    pub max_pwm: u8,
+    /// temperature sources driving the fan, see `qute monitor --help`
+    pub sources: Vec<String>,
+    /// used by `qute monitor` instead of its method, in zones the fan is first of
+    pub curve: Option<Curve>,
+    pub smoothing: Smoothing,
+}
+
+impl Default for FanConfig {
+    fn default() -> Self {
+        Self {
+            min_speed: 0,
+            max_speed: 1700,
+            min_pwm: 0,
+            max_pwm: 0xff,
+            sources: vec![String::from("ec:cpu0"), String::from("disk")],
+            curve: None,
+            smoothing: Smoothing::default(),
+        }
+    }
+}
+
+/// defaults of zones of `qute monitor`, its options take precedence
+pub struct MonitorConfig {
+    pub min_temp: f32,
+    pub max_temp: f32,
+    pub method: Method,
+    pub pid: PidParams,
+    /// time between adjustments
+    pub interval: Duration,
+    pub sysfs: PathBuf,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            min_temp: 5.0,
+            max_temp: 50.0,
+            method: Method::default(),
+            pid: PidParams::default(),
+            interval: Duration::from_secs(5),
+            sysfs: PathBuf::from("/sys"),
+        }
+    }
+}
+
+/// a temperature source of a zone
+#[derive(Debug, Clone)]
+pub struct ZoneSourceConfig {
+    /// see `qute monitor --help`
+    pub spec: String,
+    /// added to its temperatures, in ℃
+    pub offset: f32,
+    /// scales the PWM it asks for
+    pub weight: f32,
+    /// used instead of the method of the zone
+    pub curve: Option<Curve>,
+}
+
+impl ZoneSourceConfig {
+    pub fn new(spec: String) -> Self {
+        Self {
+            spec,
+            offset: 0.0,
+            weight: 1.0,
+            curve: None,
+        }
+    }
+}
+
+/// fans of `qute monitor` driven by the same temperatures
+#[derive(Debug, Clone)]
+pub struct ZoneConfig {
+    pub name: String,
+    /// fan indexes, as `qute fan --index`
+    pub fans: Vec<u8>,
+    pub sources: Vec<ZoneSourceConfig>,
+    pub min_temp: f32,
+    pub max_temp: f32,
+    pub method: Method,
+    pub smoothing: Smoothing,
+    pub pid: PidParams,
+}
+
+/// unit of printed temperatures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TempUnit {
+    Celsius,
+    Fahrenheit,
+    Both,
+}
+
+impl FromStr for TempUnit {
+    type Err = anyhow::Error;
+    fn from_str(src: &str) -> std::result::Result<Self, Self::Err> {
+        match src.trim().to_lowercase().as_str() {
+            "c" | "celsius" => Ok(TempUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TempUnit::Fahrenheit),
+            "both" => Ok(TempUnit::Both),
+            _ => Err(anyhow!("must be one of celsius|fahrenheit|both")),
+        }
+    }
+}
+
+/// output preferences; `-v` and `-q` take precedence
+pub struct OutputConfig {
+    pub unit: TempUnit,
+    pub verbose: usize,
+    pub quiet: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            unit: TempUnit::Both,
+            verbose: 0,
+            quiet: false,
+        }
+    }
 }
 
+impl OutputConfig {
+    /// format temperature in ℃ with the preferred unit
+    pub fn temp(&self, temp: f32) -> String {
+        let f = crate::utils::temp_c2f(temp);
+        match self.unit {
+            TempUnit::Celsius => format!("{:.1} ℃", temp),
+            TempUnit::Fahrenheit => format!("{:.1} ℉", f),
+            TempUnit::Both => format!("{:.1} ℃ / {:.1} ℉", temp, f),
+        }
+    }
+}
+
+/// config file, in JSON:
+///
+/// ```json
+/// {
+///   "fans": [{
+///     "min_speed": 0, "max_speed": 1700, "min_pwm": 0, "max_pwm": 255,
+///     "sources": ["ec:cpu0", "disk"],
+///     "curve": [[30, 60], [45, 150], [55, 255]],
+///     "hysteresis": 2, "ramp_up": 20, "ramp_down": 5, "dwell": 30, "zero_below": 35
+///   }],
+///   "sensors": { "sys0": "board" },
+///   "monitor": {
+///     "min": 5, "max": 50, "method": "linear", "interval": 5, "sysfs": "/sys",
+///     "pid": { "setpoint": 40, "kp": 10, "ki": 0.5, "kd": 0 }
+///   },
+///   "zones": [{
+///     "name": "system", "fans": [0], "min": 5, "max": 50, "method": "linear",
+///     "sources": ["disk", { "source": "ec:cpu0", "offset": -15, "weight": 1, "curve": [[40, 60]] }]
+///   }],
+///   "port": { "backend": "io" },
+///   "lock": { "key": 17731, "timeout": 50 },
+///   "bays": [{ "ata": 1, "port": 1 }, { "ata": 2, "port": 2 }, 3, 4],
+///   "output": { "unit": "both", "verbose": 0, "quiet": false }
+/// }
+/// ```
+///
+/// every field is optional
+#[derive(Default)]
 pub struct Config {
     pub fan: Vec<FanConfig>,
+    /// labels of EC sensors, shown along with sensor names
+    pub sensors: HashMap<SensorId, String>,
+    pub monitor: MonitorConfig,
+    /// fans driven by `qute monitor`; a zone of all fans if none configured
+    pub zones: Vec<ZoneConfig>,
+    pub platform: PlatformOptions,
+    pub output: OutputConfig,
+}
+
+impl Config {
+    /// load the config file; defaults if `path` is None and the default file does not exist
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(DEFAULT_PATH), false),
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::with_defaults());
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read config file {}", path.display()))
+            }
+        };
+        Self::parse(&text).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// the config when no config file exists
+    pub fn with_defaults() -> Self {
+        let mut config = Self {
+            fan: vec![FanConfig::default()],
+            ..Default::default()
+        };
+        config.zones = vec![config.zone(String::from("default"), vec![0])];
+        config
+    }
+
+    /// zone of fans, set up by its first fan and the monitor section
+    fn zone(&self, name: String, fans: Vec<u8>) -> ZoneConfig {
+        let m = &self.monitor;
+        let fan = fans.first().and_then(|&v| self.fan.get(v as usize));
+        let method = match fan.and_then(|v| v.curve.clone()) {
+            Some(curve) => Method::Map(Mapping::Curve(curve)),
+            None => m.method.clone(),
+        };
+        let sources = fan
+            .map(|v| v.sources.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(ZoneSourceConfig::new)
+            .collect();
+        ZoneConfig {
+            name,
+            fans,
+            sources,
+            min_temp: m.min_temp,
+            max_temp: m.max_temp,
+            method,
+            smoothing: fan.map(|v| v.smoothing.clone()).unwrap_or_default(),
+            pid: m.pid.clone(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        // the error tells line and column
+        let obj = json::parse(text)?;
+        let root = Field::root(&obj);
+        root.check_keys(&[
+            "fans", "sensors", "monitor", "zones", "port", "lock", "bays", "output",
+        ])?;
+        let mut config = Self::with_defaults();
+        if let Some(fans) = root.get("fans") {
+            config.fan = fans
+                .items()?
+                .iter()
+                .map(parse_fan)
+                .collect::<Result<Vec<_>>>()?;
+            if config.fan.is_empty() {
+                return Err(fans.error("at least one fan is required"));
+            }
+        }
+        if let Some(sensors) = root.get("sensors") {
+            for (sensor, label) in sensors.entries()? {
+                let id = sensor.parse::<SensorId>().map_err(|e| label.error(e))?;
+                config.sensors.insert(id, label.string()?);
+            }
+        }
+        if let Some(monitor) = root.get("monitor") {
+            monitor.check_keys(&["min", "max", "method", "pid", "interval", "sysfs"])?;
+            let m = &mut config.monitor;
+            if let Some(v) = monitor.get("min") {
+                m.min_temp = v.number()?;
+            }
+            if let Some(v) = monitor.get("max") {
+                m.max_temp = v.number()?;
+            }
+            if m.min_temp >= m.max_temp {
+                return Err(monitor.error("min must be less than max"));
+            }
+            if let Some(v) = monitor.get("method") {
+                m.method = v.parse()?;
+            }
+            if let Some(pid) = monitor.get("pid") {
+                parse_pid(&pid, &mut m.pid)?;
+            }
+            if let Some(v) = monitor.get("interval") {
+                let secs = v.integer(1, 3600)?;
+                m.interval = Duration::from_secs(secs);
+            }
+            if let Some(v) = monitor.get("sysfs") {
+                m.sysfs = PathBuf::from(v.string()?);
+            }
+        }
+        let all = (0..config.fan.len() as u8).collect();
+        config.zones = vec![config.zone(String::from("default"), all)];
+        if let Some(zones) = root.get("zones") {
+            let items = zones.items()?;
+            config.zones = items
+                .iter()
+                .enumerate()
+                .map(|(i, zone)| config.parse_zone(i, zone))
+                .collect::<Result<Vec<_>>>()?;
+            if config.zones.is_empty() {
+                return Err(zones.error("at least one zone is required"));
+            }
+            // a fan driven by two zones would flap between them
+            for (i, zone) in config.zones.iter().enumerate() {
+                let dup = config.zones[..i].iter().find_map(|v| {
+                    zone.fans
+                        .iter()
+                        .find(|f| v.fans.contains(f))
+                        .map(|f| (f, v))
+                });
+                if let Some((fan, other)) = dup {
+                    return Err(anyhow!(
+                        "{}.fans: fan {} is already in zone {}",
+                        items[i].path,
+                        fan,
+                        other.name
+                    ));
+                }
+            }
+        }
+        if let Some(port) = root.get("port") {
+            port.check_keys(&["backend"])?;
+            if let Some(v) = port.get("backend") {
+                config.platform.backend = v.parse::<PortBackend>()?;
+            }
+        }
+        if let Some(lock) = root.get("lock") {
+            lock.check_keys(&["key", "timeout"])?;
+            if let Some(v) = lock.get("key") {
+                config.platform.lock_key = v.integer(0, i32::MAX as u64)? as i32;
+            }
+            if let Some(v) = lock.get("timeout") {
+                config.platform.lock_timeout = Duration::from_millis(v.integer(5, 60_000)?);
+            }
+        }
+        if let Some(bays) = root.get("bays") {
+            let wirings = bays
+                .items()?
+                .iter()
+                .map(parse_bay)
+                .collect::<Result<Vec<_>>>()?;
+            if wirings.is_empty() {
+                return Err(bays.error("at least one bay is required"));
+            }
+            config.platform.bays = Some(wirings);
+        }
+        if let Some(output) = root.get("output") {
+            output.check_keys(&["unit", "verbose", "quiet"])?;
+            if let Some(v) = output.get("unit") {
+                config.output.unit = v.parse()?;
+            }
+            if let Some(v) = output.get("verbose") {
+                config.output.verbose = v.integer(0, 4)? as usize;
+            }
+            if let Some(v) = output.get("quiet") {
+                config.output.quiet = v.boolean()?;
+            }
+        }
+        Ok(config)
+    }
+
+    fn parse_zone(&self, index: usize, zone: &Field<'_>) -> Result<ZoneConfig> {
+        let keys = [
+            &[
+                "name", "fans", "sources", "min", "max", "method", "curve", "pid",
+            ][..],
+            &SMOOTHING_KEYS[..],
+        ]
+        .concat();
+        zone.check_keys(&keys)?;
+        let name = match zone.get("name") {
+            Some(v) => v.string()?,
+            None => format!("zone{}", index),
+        };
+        let fans = zone
+            .get("fans")
+            .ok_or_else(|| anyhow!("{}: required", zone.child("fans")))?;
+        let max = self.fan.len() as u64 - 1;
+        let indexes = fans
+            .items()?
+            .iter()
+            .map(|v| v.integer(0, max).map(|v| v as u8))
+            .collect::<Result<Vec<_>>>()?;
+        if indexes.is_empty() {
+            return Err(fans.error("at least one fan is required"));
+        }
+        let mut res = self.zone(name, indexes);
+        if let Some(v) = zone.get("min") {
+            res.min_temp = v.number()?;
+        }
+        if let Some(v) = zone.get("max") {
+            res.max_temp = v.number()?;
+        }
+        if res.min_temp >= res.max_temp {
+            return Err(zone.error("min must be less than max"));
+        }
+        match (zone.get("method"), zone.get("curve")) {
+            (Some(_), Some(v)) => return Err(v.error("either method or curve, not both")),
+            (Some(v), None) => res.method = v.parse()?,
+            (None, Some(v)) => res.method = Method::Map(Mapping::Curve(v.curve()?)),
+            (None, None) => {}
+        }
+        if let Some(v) = zone.get("pid") {
+            parse_pid(&v, &mut res.pid)?;
+        }
+        parse_smoothing(zone, &mut res.smoothing)?;
+        if let Some(sources) = zone.get("sources") {
+            res.sources = sources
+                .items()?
+                .iter()
+                .map(parse_zone_source)
+                .collect::<Result<Vec<_>>>()?;
+        }
+        if res.sources.is_empty() {
+            return Err(anyhow!(
+                "{}: at least one source is required",
+                zone.child("sources")
+            ));
+        }
+        Ok(res)
+    }
+
+    /// label of EC sensor, e.g. `sys0 (board)`
+    pub fn sensor_label(&self, sensor: SensorId) -> String {
+        match self.sensors.get(&sensor) {
+            Some(label) => format!("{} ({})", sensor, label),
+            None => sensor.to_string(),
+        }
+    }
+}
+
+/// fields of fans and zones, see `Smoothing`
+const SMOOTHING_KEYS: [&str; 5] = ["hysteresis", "ramp_up", "ramp_down", "dwell", "zero_below"];
+
+fn parse_fan(fan: &Field<'_>) -> Result<FanConfig> {
+    let keys = [
+        &[
+            "min_speed",
+            "max_speed",
+            "min_pwm",
+            "max_pwm",
+            "sources",
+            "curve",
+        ][..],
+        &SMOOTHING_KEYS[..],
+    ]
+    .concat();
+    fan.check_keys(&keys)?;
+    let mut res = FanConfig::default();
+    if let Some(v) = fan.get("min_speed") {
+        res.min_speed = v.integer(0, u16::MAX as u64)? as u16;
+    }
+    if let Some(v) = fan.get("max_speed") {
+        res.max_speed = v.integer(1, u16::MAX as u64)? as u16;
+    }
+    if res.min_speed > res.max_speed {
+        return Err(fan.error("min_speed must not exceed max_speed"));
+    }
+    if let Some(v) = fan.get("min_pwm") {
+        res.min_pwm = v.integer(0, 255)? as u8;
+    }
+    if let Some(v) = fan.get("max_pwm") {
+        res.max_pwm = v.integer(1, 255)? as u8;
+    }
+    if res.min_pwm > res.max_pwm {
+        return Err(fan.error("min_pwm must not exceed max_pwm"));
+    }
+    if let Some(v) = fan.get("sources") {
+        res.sources = v
+            .items()?
+            .iter()
+            .map(|v| v.string())
+            .collect::<Result<Vec<_>>>()?;
+    }
+    if let Some(v) = fan.get("curve") {
+        res.curve = Some(v.curve()?);
+    }
+    parse_smoothing(fan, &mut res.smoothing)?;
+    Ok(res)
+}
+
+/// `"disk"`, or `{ "source": "disk", "offset": -5, "weight": 1, "curve": [[30, 60]] }`
+fn parse_zone_source(source: &Field<'_>) -> Result<ZoneSourceConfig> {
+    if source.value.is_string() {
+        return Ok(ZoneSourceConfig::new(source.string()?));
+    }
+    source.check_keys(&["source", "offset", "weight", "curve"])?;
+    let spec = source
+        .get("source")
+        .ok_or_else(|| anyhow!("{}: required", source.child("source")))?;
+    let mut res = ZoneSourceConfig::new(spec.string()?);
+    if let Some(v) = source.get("offset") {
+        res.offset = v.number()?;
+    }
+    if let Some(v) = source.get("weight") {
+        res.weight = v.gain()?;
+    }
+    if let Some(v) = source.get("curve") {
+        res.curve = Some(v.curve()?);
+    }
+    Ok(res)
+}
+
+/// libata port, e.g. `3`, or `{ "ata": 3, "port": 1 }` with the port id of EC too
+fn parse_bay(bay: &Field<'_>) -> Result<BayWiring> {
+    if bay.value.is_number() {
+        return Ok(BayWiring {
+            ata_port: bay.integer(0, u32::MAX as u64)? as u32,
+            port_id: None,
+        });
+    }
+    bay.check_keys(&["ata", "port"])?;
+    let ata = bay
+        .get("ata")
+        .ok_or_else(|| anyhow!("{}: required", bay.child("ata")))?;
+    Ok(BayWiring {
+        ata_port: ata.integer(0, u32::MAX as u64)? as u32,
+        port_id: match bay.get("port") {
+            Some(v) => Some(v.integer(1, 255)? as u8),
+            None => None,
+        },
+    })
+}
+
+fn parse_smoothing(obj: &Field<'_>, s: &mut Smoothing) -> Result<()> {
+    if let Some(v) = obj.get("hysteresis") {
+        s.hysteresis = v.number()?;
+    }
+    if let Some(v) = obj.get("ramp_up") {
+        s.ramp_up = Some(v.number()?);
+    }
+    if let Some(v) = obj.get("ramp_down") {
+        s.ramp_down = Some(v.number()?);
+    }
+    if let Some(v) = obj.get("dwell") {
+        s.dwell = Duration::from_secs(v.integer(0, 3600)?);
+    }
+    if let Some(v) = obj.get("zero_below") {
+        s.zero_below = Some(v.number()?);
+    }
+    Ok(())
+}
+
+fn parse_pid(pid: &Field<'_>, params: &mut PidParams) -> Result<()> {
+    pid.check_keys(&["setpoint", "kp", "ki", "kd"])?;
+    if let Some(v) = pid.get("setpoint") {
+        params.setpoint = v.number()?;
+    }
+    if let Some(v) = pid.get("kp") {
+        params.kp = v.gain()?;
+    }
+    if let Some(v) = pid.get("ki") {
+        params.ki = v.gain()?;
+    }
+    if let Some(v) = pid.get("kd") {
+        params.kd = v.gain()?;
+    }
+    Ok(())
+}
+
+/// a JSON value with its path in the config, e.g. `fans[0].max_speed`
+struct Field<'a> {
+    path: String,
+    value: &'a JsonValue,
+}
+
+impl<'a> Field<'a> {
+    fn root(value: &'a JsonValue) -> Self {
+        Self {
+            path: String::new(),
+            value,
+        }
+    }
+
+    fn error(&self, msg: impl fmt::Display) -> anyhow::Error {
+        if self.path.is_empty() {
+            anyhow!("{}", msg)
+        } else {
+            anyhow!("{}: {}", self.path, msg)
+        }
+    }
+
+    fn child(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    /// must be an object without unknown keys, which are likely typos
+    fn check_keys(&self, keys: &[&str]) -> Result<()> {
+        if !self.value.is_object() {
+            return Err(self.error("expected an object"));
+        }
+        for (key, _) in self.value.entries() {
+            if !keys.contains(&key) {
+                return Err(anyhow!("{}: unknown field", self.child(key)));
+            }
+        }
+        Ok(())
+    }
+
+    /// field of object; None if absent or null
+    fn get(&self, key: &str) -> Option<Field<'a>> {
+        let value = &self.value[key];
+        if value.is_null() {
+            return None;
+        }
+        Some(Field {
+            path: self.child(key),
+            value,
+        })
+    }
+
+    fn items(&self) -> Result<Vec<Field<'a>>> {
+        if !self.value.is_array() {
+            return Err(self.error("expected an array"));
+        }
+        Ok(self
+            .value
+            .members()
+            .enumerate()
+            .map(|(i, value)| Field {
+                path: format!("{}[{}]", self.path, i),
+                value,
+            })
+            .collect())
+    }
+
+    /// (key, value) of object
+    fn entries(&self) -> Result<Vec<(&'a str, Field<'a>)>> {
+        if !self.value.is_object() {
+            return Err(self.error("expected an object"));
+        }
+        Ok(self
+            .value
+            .entries()
+            .map(|(key, value)| {
+                let path = self.child(key);
+                (key, Field { path, value })
+            })
+            .collect())
+    }
+
+    fn string(&self) -> Result<String> {
+        self.value
+            .as_str()
+            .map(|v| v.to_owned())
+            .ok_or_else(|| self.error("expected a string"))
+    }
+
+    fn boolean(&self) -> Result<bool> {
+        self.value
+            .as_bool()
+            .ok_or_else(|| self.error("expected true or false"))
+    }
+
+    fn number(&self) -> Result<f32> {
+        self.value
+            .as_f32()
+            .ok_or_else(|| self.error("expected a number"))
+    }
+
+    /// a number not below 0
+    fn gain(&self) -> Result<f32> {
+        match self.number()? {
+            v if v >= 0.0 => Ok(v),
+            _ => Err(self.error("must not be negative")),
+        }
+    }
+
+    fn integer(&self, min: u64, max: u64) -> Result<u64> {
+        match self.value.as_u64() {
+            Some(v) if v >= min && v <= max => Ok(v),
+            _ => Err(self.error(format!("expected an integer {}-{}", min, max))),
+        }
+    }
+
+    /// `[[temp, pwm], ...]`
+    fn curve(&self) -> Result<Curve> {
+        let points = self
+            .items()?
+            .iter()
+            .map(|point| {
+                let items = point.items()?;
+                if items.len() != 2 {
+                    return Err(point.error("expected [temp, pwm]"));
+                }
+                Ok((items[0].number()?, items[1].integer(0, 255)? as u8))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Curve::new(points).map_err(|e| self.error(e))
+    }
+
+    fn parse<T: FromStr>(&self) -> Result<T>
+    where
+        T::Err: fmt::Display,
+    {
+        self.string()?.parse().map_err(|e| self.error(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qute_ctrl::testutil::TempDir;
+
+    #[test]
+    fn parse_full() {
+        let config = Config::parse(
+            r#"{
+                "fans": [
+                    { "max_speed": 2000, "sources": ["ec:cpu0"], "curve": [[30, 60], [50, 255]] },
+                    { "hysteresis": 2.5, "dwell": 30, "zero_below": 35 }
+                ],
+                "sensors": { "sys0": "board" },
+                "monitor": {
+                    "min": 20, "max": 45.5, "method": "pid", "interval": 10,
+                    "pid": { "setpoint": 38, "kd": 1.5 }
+                },
+                "port": { "backend": "file" },
+                "lock": { "key": 1234, "timeout": 100 },
+                "bays": [{ "ata": 3, "port": 2 }, 4],
+                "output": { "unit": "celsius", "verbose": 2 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.fan.len(), 2);
+        assert_eq!(config.fan[0].max_speed, 2000);
+        assert_eq!(config.fan[0].sources, vec!["ec:cpu0"]);
+        assert_eq!(config.fan[0].curve.as_ref().unwrap().pwm(40.0), 158);
+        assert_eq!(config.fan[1].max_speed, 1700);
+        assert!(config.fan[1].curve.is_none());
+        assert_eq!(config.fan[1].smoothing.hysteresis, 2.5);
+        assert_eq!(config.fan[1].smoothing.dwell, Duration::from_secs(30));
+        assert_eq!(config.fan[1].smoothing.zero_below, Some(35.0));
+        assert_eq!(config.sensor_label(SensorId(5)), "sys0 (board)");
+        assert_eq!(config.sensor_label(SensorId(0)), "cpu0");
+        assert_eq!(config.monitor.max_temp, 45.5);
+        assert_eq!(config.monitor.method, Method::Pid);
+        assert_eq!(config.monitor.pid.setpoint, 38.0);
+        assert_eq!(config.monitor.pid.kd, 1.5);
+        assert_eq!(config.monitor.pid.kp, PidParams::default().kp);
+        assert_eq!(config.monitor.interval, Duration::from_secs(10));
+        assert_eq!(config.monitor.sysfs, PathBuf::from("/sys"));
+        assert_eq!(config.platform.backend, PortBackend::File);
+        assert_eq!(config.platform.lock_key, 1234);
+        assert_eq!(config.platform.cmd_port, 0x6c);
+        let bays = config.platform.bays.as_ref().unwrap();
+        assert_eq!(bays[0].ata_port, 3);
+        assert_eq!(bays[0].port_id, Some(2));
+        assert_eq!((bays[1].ata_port, bays[1].port_id), (4, None));
+        assert_eq!(config.output.unit, TempUnit::Celsius);
+        assert_eq!(config.output.verbose, 2);
+        assert!(!config.output.quiet);
+    }
+
+    #[test]
+    fn parse_empty() {
+        let config = Config::parse("{}").unwrap();
+        assert_eq!(config.fan.len(), 1);
+        assert_eq!(config.fan[0].max_speed, 1700);
+        assert_eq!(config.monitor.min_temp, 5.0);
+        assert_eq!(config.output.unit, TempUnit::Both);
+        assert_eq!(config.zones.len(), 1);
+        assert_eq!(config.zones[0].fans, vec![0]);
+        assert_eq!(config.zones[0].sources[1].spec, "disk");
+        assert_eq!(config.zones[0].method, Method::Map(Mapping::Linear));
+    }
+
+    #[test]
+    fn parse_zones() {
+        let config = Config::parse(
+            r#"{
+                "fans": [{ "curve": [[30, 60], [50, 255]], "hysteresis": 1 }, {}],
+                "zones": [
+                    { "name": "psu", "fans": [1], "sources": [{ "source": "ec:power0" }] },
+                    {
+                        "name": "system", "fans": [0], "hysteresis": 2, "max": 55,
+                        "sources": [
+                            "disk",
+                            { "source": "ec:cpu0", "offset": -15, "weight": 0.8, "curve": [[40, 60]] }
+                        ]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (psu, system) = (&config.zones[0], &config.zones[1]);
+        assert_eq!(psu.name, "psu");
+        assert_eq!(psu.fans, vec![1]);
+        assert_eq!(psu.sources.len(), 1);
+        assert_eq!(psu.sources[0].weight, 1.0);
+        assert_eq!(psu.method, Method::Map(Mapping::Linear));
+        assert_eq!(system.fans, vec![0]);
+        // set up by its first fan
+        assert!(matches!(system.method, Method::Map(Mapping::Curve(_))));
+        assert_eq!(system.smoothing.hysteresis, 2.0);
+        assert_eq!(system.max_temp, 55.0);
+        assert_eq!(system.sources[0].offset, 0.0);
+        assert_eq!(system.sources[1].offset, -15.0);
+        assert_eq!(system.sources[1].weight, 0.8);
+        assert!(system.sources[1].curve.is_some());
+    }
+
+    #[test]
+    fn syntax_error_position() {
+        let err = Config::parse("{\n  \"fans\": [}\n").err().unwrap();
+        assert!(err.to_string().contains("2:12"), "{}", err);
+    }
+
+    #[test]
+    fn field_errors() {
+        let cases = [
+            (r#"{"fans": [{}, {"max_speed": -1}]}"#, "fans[1].max_speed"),
+            (
+                r#"{"fans": [{"max_spede": 1}]}"#,
+                "fans[0].max_spede: unknown",
+            ),
+            (r#"{"fans": []}"#, "fans: at least one"),
+            (
+                r#"{"fans": [{"curve": [[30, 60], [40]]}]}"#,
+                "fans[0].curve[1]: expected [temp",
+            ),
+            (
+                r#"{"fans": [{"curve": [[40, 60], [30, 90]]}]}"#,
+                "fans[0].curve: temperatures",
+            ),
+            (
+                r#"{"sensors": {"gpu0": "x"}}"#,
+                "sensors.gpu0: invalid sensor",
+            ),
+            (r#"{"monitor": {"min": 60}}"#, "monitor: min must be less"),
+            (r#"{"monitor": {"method": "fast"}}"#, "monitor.method"),
+            (r#"{"zones": [{}]}"#, "zones[0].fans: required"),
+            (r#"{"bays": [{"port": 1}]}"#, "bays[0].ata: required"),
+            (
+                r#"{"bays": [2, {"ata": 1, "port": 0}]}"#,
+                "bays[1].port: expected an integer 1-255",
+            ),
+            (
+                r#"{"zones": [{"fans": [1]}]}"#,
+                "zones[0].fans[0]: expected an integer 0-0",
+            ),
+            (
+                r#"{"fans": [{}, {}], "zones": [{"fans": [0]}, {"fans": [1, 0]}]}"#,
+                "zones[1].fans: fan 0 is already in zone zone0",
+            ),
+            (
+                r#"{"zones": [{"fans": [0], "sources": [{"offset": 1}]}]}"#,
+                "zones[0].sources[0].source: required",
+            ),
+            (
+                r#"{"zones": [{"fans": [0], "method": "step", "curve": [[30, 60]]}]}"#,
+                "zones[0].curve: either",
+            ),
+            (
+                r#"{"zones": [{"fans": [0], "sources": []}]}"#,
+                "zones[0].sources: at least",
+            ),
+            (
+                r#"{"monitor": {"pid": {"ki": -1}}}"#,
+                "monitor.pid.ki: must not",
+            ),
+            (
+                r#"{"fans": [{"min_pwm": 200, "max_pwm": 100}]}"#,
+                "fans[0]: min_pwm",
+            ),
+            (r#"{"port": {"backend": "mmio"}}"#, "port.backend"),
+            (r#"{"output": {"quiet": 1}}"#, "output.quiet: expected true"),
+            (r#"[]"#, "expected an object"),
+        ];
+        for &(text, expected) in cases.iter() {
+            let err = Config::parse(text).err().unwrap().to_string();
+            assert!(err.starts_with(expected), "{}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn load_missing_default() {
+        let dir = TempDir::new("config");
+        let path = dir.join("qute.json");
+        assert!(Config::load(Some(&path)).is_err());
+        dir.write("qute.json", r#"{"output": {"quiet": true}}"#);
+        assert!(Config::load(Some(&path)).unwrap().output.quiet);
+    }
 }
@@ -12,17 +12,18 @@ pub struct Options {
 }
 
 pub struct Context {
-    config: Config,
+    /// an error loading the config file is kept until the config is needed, so that help works
+    config: Result<Config>,
     opts: Options,
 }
 
 impl Context {
-    pub fn new(config: Config, opts: Options) -> Self {
+    pub fn new(config: Result<Config>, opts: Options) -> Self {
         Self { config, opts }
     }
 
-    pub fn get_config(&self) -> &Config {
-        &self.config
+    pub fn get_config(&self) -> Result<&Config> {
+        self.config.as_ref().map_err(|e| anyhow!("{:#}", e))
     }
 
     pub fn get_opts(&self) -> &Options {
@@ -30,6 +31,6 @@ impl Context {
     }
 
     pub fn get_platform(&self) -> Result<Platform> {
-        Ok(Platform::with_default()?)
+        Ok(Platform::with_options(&self.get_config()?.platform)?)
     }
 }
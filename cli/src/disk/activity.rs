@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+/// activity LEDs of bays, blinking on I/O of the disks in them
+#[derive(Default)]
+pub struct Activity {
+    /// (port id, disk name) of bays holding a disk
+    ports: Vec<(u8, String)>,
+    /// I/O counter of each disk at the last sample
+    last: HashMap<String, u64>,
+    /// LEDs turned on
+    lit: Vec<u8>,
+}
+
+impl Activity {
+    pub fn new(ports: Vec<(u8, String)>) -> Self {
+        Self {
+            ports,
+            ..Default::default()
+        }
+    }
+
+    /// remap bays, e.g. after hot-swap; return LED changes, LEDs of bays left empty are turned off
+    pub fn set_ports(&mut self, ports: Vec<(u8, String)>) -> Vec<(u8, bool)> {
+        let mut res = vec![];
+        let ports_ref = &ports;
+        self.lit.retain(|port| {
+            let kept = ports_ref.iter().any(|(v, _)| v == port);
+            if !kept {
+                res.push((*port, false));
+            }
+            kept
+        });
+        self.ports = ports;
+        res
+    }
+
+    /// LED changes from I/O counters of disks; a LED toggles while its disk is busy, and is off
+    /// while idle
+    pub fn update(&mut self, stats: &HashMap<String, u64>) -> Vec<(u8, bool)> {
+        let mut res = vec![];
+        for (port, name) in self.ports.iter() {
+            let count = match stats.get(name) {
+                Some(&v) => v,
+                None => continue,
+            };
+            let busy = self
+                .last
+                .insert(name.clone(), count)
+                .map(|v| v != count)
+                .unwrap_or(false);
+            let lit = self.lit.contains(port);
+            let next = busy && !lit;
+            if next != lit {
+                res.push((*port, next));
+                if next {
+                    self.lit.push(*port);
+                } else {
+                    self.lit.retain(|v| v != port);
+                }
+            }
+        }
+        res
+    }
+
+    /// turn off all LEDs turned on
+    pub fn off(&mut self) -> Vec<(u8, bool)> {
+        self.lit.drain(..).map(|v| (v, false)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(sda: u64, sdb: u64) -> HashMap<String, u64> {
+        let mut res = HashMap::new();
+        res.insert(String::from("sda"), sda);
+        res.insert(String::from("sdb"), sdb);
+        res
+    }
+
+    #[test]
+    fn blink_on_io() {
+        let mut leds = Activity::new(vec![(1, String::from("sda")), (2, String::from("sdb"))]);
+        assert!(leds.update(&stats(100, 50)).is_empty());
+        // sda busy: on, then toggles while busy
+        assert_eq!(leds.update(&stats(110, 50)), vec![(1, true)]);
+        assert_eq!(leds.update(&stats(120, 50)), vec![(1, false)]);
+        assert_eq!(leds.update(&stats(130, 50)), vec![(1, true)]);
+        // sda idle: off; no write while unchanged
+        assert_eq!(leds.update(&stats(130, 55)), vec![(1, false), (2, true)]);
+        assert_eq!(leds.update(&stats(130, 55)), vec![(2, false)]);
+        assert!(leds.update(&stats(130, 55)).is_empty());
+    }
+
+    #[test]
+    fn remap_and_off() {
+        let mut leds = Activity::new(vec![(1, String::from("sda")), (2, String::from("sdb"))]);
+        leds.update(&stats(0, 0));
+        leds.update(&stats(1, 1));
+        assert_eq!(
+            leds.set_ports(vec![(1, String::from("sda"))]),
+            vec![(2, false)]
+        );
+        assert_eq!(leds.off(), vec![(1, false)]);
+        assert!(leds.off().is_empty());
+    }
+}
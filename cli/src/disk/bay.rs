@@ -0,0 +1,115 @@
+use crate::state;
+use anyhow::Result;
+use json::JsonValue;
+
+const STATE_FILE: &str = "bays.json";
+
+/// power and LEDs of a bay as last set by qute; EC reads back neither, so they are kept in a
+/// state file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BayRecord {
+    /// port id of EC
+    pub port_id: u8,
+    /// None if never set by qute
+    pub power: Option<bool>,
+    pub ident: bool,
+    pub error: bool,
+}
+
+impl BayRecord {
+    fn to_json(&self) -> JsonValue {
+        json::object! {
+            port_id: self.port_id,
+            power: self.power,
+            ident: self.ident,
+            error: self.error,
+        }
+    }
+
+    fn from_json(obj: &JsonValue) -> Option<Self> {
+        let power = match &obj["power"] {
+            JsonValue::Null => None,
+            v => Some(v.as_bool()?),
+        };
+        Some(Self {
+            port_id: obj["port_id"].as_u8()?,
+            power,
+            ident: obj["ident"].as_bool()?,
+            error: obj["error"].as_bool()?,
+        })
+    }
+
+    /// LEDs lit, e.g. `ident,error`; `-` if none
+    pub fn leds(&self) -> String {
+        let leds: Vec<&str> = [(self.ident, "ident"), (self.error, "error")]
+            .iter()
+            .filter(|v| v.0)
+            .map(|v| v.1)
+            .collect();
+        if leds.is_empty() {
+            String::from("-")
+        } else {
+            leds.join(",")
+        }
+    }
+}
+
+/// records of all bays from the state file
+pub fn load_all() -> Result<Vec<BayRecord>> {
+    let obj = match state::load(STATE_FILE)? {
+        Some(v) => v,
+        None => return Ok(vec![]),
+    };
+    obj["bays"]
+        .members()
+        .map(|v| {
+            BayRecord::from_json(v)
+                .ok_or_else(|| anyhow!("invalid state file {}", state::path(STATE_FILE).display()))
+        })
+        .collect()
+}
+
+/// record a change made to the bay of port id; a failure is only logged, as the change is made
+pub fn record<F: FnOnce(&mut BayRecord)>(port_id: u8, change: F) {
+    let res = load_all().and_then(|mut items| {
+        let pos = match items.iter().position(|v| v.port_id == port_id) {
+            Some(pos) => pos,
+            None => {
+                items.push(BayRecord {
+                    port_id,
+                    ..Default::default()
+                });
+                items.len() - 1
+            }
+        };
+        change(&mut items[pos]);
+        items.sort_by_key(|v| v.port_id);
+        let bays: Vec<JsonValue> = items.iter().map(|v| v.to_json()).collect();
+        state::save(STATE_FILE, &json::object! { bays: bays })
+    });
+    if let Err(e) = res {
+        warn!("failed to record state of bay {}: {:#}", port_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let record = BayRecord {
+            port_id: 3,
+            power: Some(false),
+            ident: true,
+            error: true,
+        };
+        let res = BayRecord::from_json(&record.to_json()).unwrap();
+        assert_eq!(res, record);
+        assert_eq!(res.leds(), "ident,error");
+        let record = BayRecord::default();
+        let res = BayRecord::from_json(&record.to_json()).unwrap();
+        assert_eq!(res.power, None);
+        assert_eq!(res.leds(), "-");
+    }
+}
@@ -0,0 +1,316 @@
+use super::bay;
+use anyhow::Result;
+use qute_ctrl::disk::{
+    self, Bay, Disk, PowerMode, Smart, ATTR_OFFLINE_UNCORRECTABLE, ATTR_PENDING_SECTORS,
+    ATTR_REALLOCATED_SECTORS, ATTR_REALLOCATION_EVENTS,
+};
+use qute_ctrl::{DiskDrive, LedControl};
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum DiskHealth {
+    Healthy,
+    /// wearing out, keep an eye on it
+    Warning,
+    /// replace it
+    Failing,
+}
+
+impl fmt::Display for DiskHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiskHealth::Healthy => write!(f, "healthy"),
+            DiskHealth::Warning => write!(f, "warning"),
+            DiskHealth::Failing => write!(f, "failing"),
+        }
+    }
+}
+
+/// health of a disk, with the reasons
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub health: DiskHealth,
+    pub reasons: Vec<String>,
+}
+
+impl Report {
+    fn push(&mut self, health: DiskHealth, reason: String) {
+        if health > self.health {
+            self.health = health;
+        }
+        self.reasons.push(reason);
+    }
+}
+
+/// critical warning bits of NVMe SMART log
+const NVME_WARNINGS: [&str; 5] = [
+    "available spare below threshold",
+    "temperature out of range",
+    "reliability degraded",
+    "read only",
+    "volatile memory backup failed",
+];
+
+/// assess SMART data, and the overall-health self-assessment if known
+pub fn assess(smart: &Smart, passed: Option<bool>) -> Report {
+    let mut report = Report {
+        health: DiskHealth::Healthy,
+        reasons: vec![],
+    };
+    if passed == Some(false) {
+        report.push(
+            DiskHealth::Failing,
+            String::from("SMART overall-health self-assessment failed"),
+        );
+    }
+    match smart {
+        Smart::Ata(smart) => {
+            let raw = |id| {
+                smart
+                    .attribute(id)
+                    .map(|v| v.raw & 0xffff_ffff)
+                    .unwrap_or(0)
+            };
+            let pending = raw(ATTR_PENDING_SECTORS);
+            if pending > 0 {
+                report.push(
+                    DiskHealth::Failing,
+                    format!("{} sectors pending reallocation", pending),
+                );
+            }
+            let uncorrectable = raw(ATTR_OFFLINE_UNCORRECTABLE);
+            if uncorrectable > 0 {
+                report.push(
+                    DiskHealth::Failing,
+                    format!("{} uncorrectable sectors", uncorrectable),
+                );
+            }
+            let reallocated = raw(ATTR_REALLOCATED_SECTORS).max(raw(ATTR_REALLOCATION_EVENTS));
+            if reallocated > 0 {
+                report.push(
+                    DiskHealth::Warning,
+                    format!("{} reallocated sectors", reallocated),
+                );
+            }
+        }
+        Smart::Nvme(smart) => {
+            for (bit, text) in NVME_WARNINGS.iter().enumerate() {
+                if smart.critical_warning & (1 << bit) != 0 {
+                    report.push(DiskHealth::Failing, format!("critical warning: {}", text));
+                }
+            }
+            if smart.percentage_used >= 100 {
+                report.push(
+                    DiskHealth::Warning,
+                    format!("{}% of rated life used", smart.percentage_used),
+                );
+            }
+            if smart.media_errors > 0 {
+                report.push(
+                    DiskHealth::Warning,
+                    format!("{} media errors", smart.media_errors),
+                );
+            }
+        }
+    }
+    report
+}
+
+/// check a disk; None if it sleeps, so that it is not woken up
+pub fn check(disk: &Disk) -> Result<Option<Report>> {
+    if disk::check_power_mode(&disk.dev).ok() == Some(PowerMode::Standby) {
+        return Ok(None);
+    }
+    let smart = disk::read_smart(&disk.dev)?;
+    let passed = disk::read_smart_status(&disk.dev).ok();
+    Ok(Some(assess(&smart, passed)))
+}
+
+/// decide error LEDs of bays; a LED is lit for a failing disk, and cleared once the disk is
+/// replaced by a healthy one, told by its serial, or taken out of the bay
+#[derive(Default)]
+pub struct ErrorLeds {
+    /// port id and serial of failing disks, whose LEDs are lit
+    failing: HashMap<u8, Option<String>>,
+    /// ports whose LEDs are known to be off
+    cleared: Vec<u8>,
+}
+
+impl ErrorLeds {
+    /// LED changes, from port id, serial and health of checked disks, and port ids of empty bays
+    pub fn update(
+        &mut self,
+        checked: &[(u8, Option<String>, DiskHealth)],
+        empty: &[u8],
+    ) -> Vec<(u8, bool)> {
+        let mut res = vec![];
+        for (port, serial, health) in checked {
+            if *health == DiskHealth::Failing {
+                if self.failing.insert(*port, serial.clone()).is_none() {
+                    self.cleared.retain(|v| v != port);
+                    res.push((*port, true));
+                }
+                continue;
+            }
+            // the same disk looking better, e.g. pending sectors remapped, is still suspect;
+            // without a serial, a replacement can only be told by its health
+            match self.failing.get(port) {
+                Some(failed) if failed.is_some() && failed == serial => continue,
+                Some(_) => {
+                    self.failing.remove(port);
+                }
+                None => {}
+            }
+            self.clear(*port, &mut res);
+        }
+        for port in empty {
+            self.failing.remove(port);
+            self.clear(*port, &mut res);
+        }
+        res
+    }
+
+    fn clear(&mut self, port: u8, res: &mut Vec<(u8, bool)>) {
+        if !self.cleared.contains(&port) {
+            self.cleared.push(port);
+            res.push((port, false));
+        }
+    }
+}
+
+/// bay, the disk in it and its report; None for a sleeping disk
+pub type BayReport = (Bay, Disk, Result<Option<Report>>);
+
+/// check disks in bays, and light error LEDs of failing ones
+pub fn check_bays<T: DiskDrive + LedControl>(
+    chip: &T,
+    leds: &mut ErrorLeds,
+) -> Result<Vec<BayReport>> {
+    let mut res = vec![];
+    let mut checked = vec![];
+    let mut empty = vec![];
+    for (bay, disk) in chip.bay_disks()? {
+        let disk = match disk {
+            Some(v) => v,
+            None => {
+                empty.push(bay.port_id);
+                continue;
+            }
+        };
+        let report = check(&disk);
+        if let Ok(Some(ref report)) = report {
+            checked.push((bay.port_id, disk.serial.clone(), report.health));
+        }
+        res.push((bay, disk, report));
+    }
+    for (port, enable) in leds.update(&checked, &empty) {
+        chip.set_disk_err_led(port, enable)?;
+        bay::record(port, |v| v.error = enable);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qute_ctrl::disk::{AtaSmart, Attribute, NvmeSmart};
+
+    fn ata(attrs: &[(u8, u64)]) -> Smart {
+        Smart::Ata(AtaSmart {
+            attributes: attrs
+                .iter()
+                .map(|&(id, raw)| Attribute {
+                    id,
+                    flags: 0,
+                    value: 100,
+                    worst: 100,
+                    raw,
+                })
+                .collect(),
+        })
+    }
+
+    fn nvme(critical_warning: u8, percentage_used: u8) -> Smart {
+        Smart::Nvme(NvmeSmart {
+            critical_warning,
+            temperature: Some(40.0),
+            available_spare: 100,
+            available_spare_threshold: 10,
+            percentage_used,
+            media_errors: 0,
+        })
+    }
+
+    #[test]
+    fn assess_ata() {
+        let report = assess(&ata(&[(5, 0), (197, 0), (198, 0)]), Some(true));
+        assert_eq!(report.health, DiskHealth::Healthy);
+        let report = assess(&ata(&[(5, 8), (197, 0)]), Some(true));
+        assert_eq!(report.health, DiskHealth::Warning);
+        let report = assess(&ata(&[(5, 8), (197, 2)]), None);
+        assert_eq!(report.health, DiskHealth::Failing);
+        assert_eq!(report.reasons.len(), 2);
+        let report = assess(&ata(&[]), Some(false));
+        assert_eq!(report.health, DiskHealth::Failing);
+    }
+
+    #[test]
+    fn assess_nvme() {
+        assert_eq!(assess(&nvme(0, 3), None).health, DiskHealth::Healthy);
+        assert_eq!(assess(&nvme(0, 100), None).health, DiskHealth::Warning);
+        let report = assess(&nvme(0b1001, 3), Some(false));
+        assert_eq!(report.health, DiskHealth::Failing);
+        assert!(report.reasons.iter().any(|v| v.contains("read only")));
+    }
+
+    #[test]
+    fn error_led_until_replaced() {
+        let mut leds = ErrorLeds::default();
+        let serial = |v: &str| Some(v.to_owned());
+        assert_eq!(
+            leds.update(
+                &[
+                    (1, serial("A"), DiskHealth::Healthy),
+                    (2, serial("B"), DiskHealth::Failing)
+                ],
+                &[]
+            ),
+            vec![(1, false), (2, true)]
+        );
+        // no repeated writes
+        assert!(leds
+            .update(
+                &[
+                    (1, serial("A"), DiskHealth::Warning),
+                    (2, serial("B"), DiskHealth::Failing)
+                ],
+                &[]
+            )
+            .is_empty());
+        // the same disk looking better stays marked
+        assert!(leds
+            .update(&[(2, serial("B"), DiskHealth::Healthy)], &[])
+            .is_empty());
+        // replaced
+        assert_eq!(
+            leds.update(&[(2, serial("C"), DiskHealth::Healthy)], &[]),
+            vec![(2, false)]
+        );
+    }
+
+    #[test]
+    fn error_led_of_emptied_bay() {
+        let mut leds = ErrorLeds::default();
+        assert_eq!(
+            leds.update(&[(3, None, DiskHealth::Failing)], &[4]),
+            vec![(3, true), (4, false)]
+        );
+        assert_eq!(leds.update(&[], &[3, 4]), vec![(3, false)]);
+        // a disk without serial is told replaced by its health
+        leds.update(&[(3, None, DiskHealth::Failing)], &[]);
+        assert_eq!(
+            leds.update(&[(3, None, DiskHealth::Healthy)], &[]),
+            vec![(3, false)]
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod activity;
+pub mod bay;
+pub mod health;
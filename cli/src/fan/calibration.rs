@@ -0,0 +1,291 @@
+use crate::{state, utils};
+use anyhow::Result;
+use json::JsonValue;
+use qute_ctrl::{platform::Platform, FanControl, FanMode};
+use std::{thread::sleep, time::Duration};
+
+/// name of the state file
+const STATE_FILE: &str = "fan-calibration.json";
+
+/// PWM step of the coarse sweep
+const SWEEP_STEP: u8 = 15;
+
+/// PWM step to search the stall floor and start PWM; EC takes PWM in percent
+const FINE_STEP: u8 = 3;
+
+/// max seconds to wait for the fan speed to settle
+const SETTLE_TIMEOUT: u32 = 15;
+
+/// how long to kick-start a stopped fan
+const KICK_TIME: Duration = Duration::from_millis(1500);
+
+/// PWM→RPM characteristics of a fan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    pub fan: u8,
+    /// (pwm, rpm), ordered by pwm
+    pub curve: Vec<(u8, u16)>,
+    /// minimal PWM to start a stopped fan
+    pub start_pwm: u8,
+    /// minimal PWM to keep a running fan spinning
+    pub sustain_pwm: u8,
+    pub max_rpm: u16,
+}
+
+impl Calibration {
+    /// sweep the PWM of fan, it takes a few minutes; the fan mode is restored afterwards
+    pub fn run(chip: &Platform, fan: u8) -> Result<Self> {
+        let mode = chip.get_fan_mode(fan)?;
+        let pwm = chip.get_fan_pwm(fan)?;
+        let res = sweep(chip, fan);
+        if mode == FanMode::Auto {
+            chip.set_fan_mode(fan, FanMode::Auto)?;
+        } else {
+            chip.set_fan_speed(fan, pwm)?;
+        }
+        res
+    }
+
+    /// raise PWM to the stall floor; 0 still stops the fan
+    pub fn floor(&self, pwm: u8) -> u8 {
+        if pwm == 0 {
+            0
+        } else {
+            pwm.max(self.sustain_pwm)
+        }
+    }
+
+    /// expected RPM at PWM, interpolated from the curve
+    pub fn expected_rpm(&self, pwm: u8) -> Option<u16> {
+        let pos = self.curve.iter().position(|&(p, _)| p >= pwm)?;
+        let (p1, r1) = self.curve[pos];
+        if p1 == pwm || pos == 0 {
+            return Some(r1);
+        }
+        let (p0, r0) = self.curve[pos - 1];
+        let ratio = (pwm - p0) as f32 / (p1 - p0) as f32;
+        let rpm = r0 as f32 + (r1 as f32 - r0 as f32) * ratio;
+        Some(rpm as u16)
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let curve: Vec<JsonValue> = self
+            .curve
+            .iter()
+            .map(|&(pwm, rpm)| json::array![pwm, rpm])
+            .collect();
+        json::object! {
+            fan: self.fan,
+            curve: curve,
+            start_pwm: self.start_pwm,
+            sustain_pwm: self.sustain_pwm,
+            max_rpm: self.max_rpm,
+        }
+    }
+
+    fn from_json(obj: &JsonValue) -> Option<Self> {
+        let curve = obj["curve"]
+            .members()
+            .map(|p| Some((p[0].as_u8()?, p[1].as_u16()?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            fan: obj["fan"].as_u8()?,
+            curve,
+            start_pwm: obj["start_pwm"].as_u8()?,
+            sustain_pwm: obj["sustain_pwm"].as_u8()?,
+            max_rpm: obj["max_rpm"].as_u16()?,
+        })
+    }
+}
+
+/// load calibrations of all fans from the state file
+pub fn load_all() -> Result<Vec<Calibration>> {
+    let obj = match state::load(STATE_FILE)? {
+        Some(v) => v,
+        None => return Ok(vec![]),
+    };
+    obj["fans"]
+        .members()
+        .map(|v| {
+            Calibration::from_json(v).ok_or_else(|| {
+                anyhow!(
+                    "invalid state file {}, run `qute fan calibrate` again",
+                    state::path(STATE_FILE).display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// load calibration of fan from the state file
+pub fn load(fan: u8) -> Result<Option<Calibration>> {
+    let res = load_all()?.into_iter().find(|v| v.fan == fan);
+    Ok(res)
+}
+
+/// save calibration to the state file, replacing the previous one of the same fan
+pub fn save(cal: &Calibration) -> Result<()> {
+    let mut items = load_all()?;
+    items.retain(|v| v.fan != cal.fan);
+    items.push(cal.clone());
+    items.sort_by_key(|v| v.fan);
+    let fans: Vec<JsonValue> = items.iter().map(|v| v.to_json()).collect();
+    state::save(STATE_FILE, &json::object! { fans: fans })
+}
+
+/// set PWM of fan; avoid PWM where the fan stalls, and kick-start the fan if it stopped.
+/// return the PWM actually set
+pub fn set_pwm(chip: &Platform, fan: u8, pwm: u8, cal: Option<&Calibration>) -> Result<u8> {
+    let cal = match cal {
+        Some(v) => v,
+        None => {
+            chip.set_fan_speed(fan, pwm)?;
+            return Ok(pwm);
+        }
+    };
+    let pwm = cal.floor(pwm);
+    if pwm > 0 && pwm < cal.start_pwm && chip.get_fan_speed(fan)? == 0 {
+        // start_pwm is the lowest PWM that started the fan once during calibration; a step above
+        // it, so that a kick still works for a fan a bit stiffer than then, e.g. when cold
+        let kick = cal.start_pwm.saturating_add(SWEEP_STEP);
+        debug!("kick-start fan {} at pwm {}", fan, kick);
+        chip.set_fan_speed(fan, kick)?;
+        sleep(KICK_TIME);
+    }
+    chip.set_fan_speed(fan, pwm)?;
+    Ok(pwm)
+}
+
+fn sweep(chip: &Platform, fan: u8) -> Result<Calibration> {
+    // coarse sweep from full speed down, until the fan stalls
+    let mut curve = vec![];
+    let mut pwm = 0xff;
+    let mut running = None;
+    let mut stalled = false;
+    loop {
+        let rpm = settle(chip, fan, pwm)?;
+        println!("fan {}: pwm {:>3} -> {:>4} RPM", fan, pwm, rpm);
+        curve.push((pwm, rpm));
+        if rpm == 0 {
+            stalled = true;
+            break;
+        }
+        running = Some(pwm);
+        if pwm == 0 {
+            break;
+        }
+        pwm = pwm.saturating_sub(SWEEP_STEP);
+    }
+    curve.reverse();
+    let max_rpm = curve.iter().map(|&(_, rpm)| rpm).max().unwrap_or(0);
+    let running = match running {
+        Some(v) => v,
+        None => return Err(anyhow!("fan calibration: fan {} does not spin", fan)),
+    };
+    if !stalled {
+        return Ok(Calibration {
+            fan,
+            curve,
+            start_pwm: 0,
+            sustain_pwm: 0,
+            max_rpm,
+        });
+    }
+
+    // fine sweep down from the last running PWM, to find the stall floor
+    settle(chip, fan, 0xff)?;
+    let mut sustain_pwm = running;
+    let mut pwm = running;
+    while pwm > FINE_STEP {
+        pwm -= FINE_STEP;
+        if settle(chip, fan, pwm)? == 0 {
+            break;
+        }
+        sustain_pwm = pwm;
+    }
+    println!("fan {}: stalls below pwm {}", fan, sustain_pwm);
+
+    // the fan stopped, step up to find the PWM that starts it again
+    let mut start_pwm = pwm;
+    while settle(chip, fan, start_pwm)? == 0 {
+        if start_pwm == 0xff {
+            return Err(anyhow!("fan calibration: fan {} does not start", fan));
+        }
+        start_pwm = start_pwm.saturating_add(FINE_STEP);
+    }
+    println!("fan {}: starts from pwm {}", fan, start_pwm);
+
+    Ok(Calibration {
+        fan,
+        curve,
+        start_pwm,
+        sustain_pwm,
+        max_rpm,
+    })
+}
+
+/// set PWM and wait for the fan speed to settle; return the settled speed
+fn settle(chip: &Platform, fan: u8, pwm: u8) -> Result<u16> {
+    chip.set_fan_speed(fan, pwm)?;
+    sleep(Duration::from_secs(1));
+    let mut last = chip.get_fan_speed(fan)?;
+    for _ in 0..SETTLE_TIMEOUT {
+        sleep(Duration::from_secs(1));
+        if utils::is_terminated() {
+            return Err(anyhow!("fan calibration: interrupted"));
+        }
+        let rpm = chip.get_fan_speed(fan)?;
+        let tolerance = (last / 50).max(20);
+        if (rpm as i32 - last as i32).abs() <= tolerance as i32 {
+            return Ok(rpm);
+        }
+        last = rpm;
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cal() -> Calibration {
+        Calibration {
+            fan: 1,
+            curve: vec![(30, 0), (60, 400), (120, 900), (255, 1800)],
+            start_pwm: 54,
+            sustain_pwm: 45,
+            max_rpm: 1800,
+        }
+    }
+
+    #[test]
+    fn stall_floor() {
+        let cal = cal();
+        assert_eq!(cal.floor(0), 0);
+        assert_eq!(cal.floor(20), 45);
+        assert_eq!(cal.floor(100), 100);
+    }
+
+    #[test]
+    fn interpolate_rpm() {
+        let cal = cal();
+        assert_eq!(cal.expected_rpm(60), Some(400));
+        assert_eq!(cal.expected_rpm(90), Some(650));
+        assert_eq!(cal.expected_rpm(255), Some(1800));
+        // below the curve, the lowest point
+        assert_eq!(cal.expected_rpm(10), Some(0));
+        let empty = Calibration {
+            curve: vec![],
+            ..cal
+        };
+        assert_eq!(empty.expected_rpm(100), None);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let cal = cal();
+        assert_eq!(Calibration::from_json(&cal.to_json()), Some(cal));
+        let obj = json::parse(r#"{"fan": 1, "curve": [[30, 0]], "start_pwm": 54}"#).unwrap();
+        assert_eq!(Calibration::from_json(&obj), None);
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// temperature→PWM mapping, linearly interpolated between points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    /// (temperature in ℃, pwm), ordered by temperature
+    points: Vec<(f32, u8)>,
+}
+
+impl Curve {
+    /// temperatures must increase and PWMs must not decrease
+    pub fn new(points: Vec<(f32, u8)>) -> Result<Self> {
+        if points.is_empty() {
+            return Err(anyhow!("curve requires at least one point"));
+        }
+        for pair in points.windows(2) {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            if t1 <= t0 {
+                return Err(anyhow!(
+                    "temperatures of curve must increase, {} ≥ {}",
+                    t0,
+                    t1
+                ));
+            }
+            if p1 < p0 {
+                return Err(anyhow!("PWMs of curve must not decrease, {} > {}", p0, p1));
+            }
+        }
+        Ok(Self { points })
+    }
+
+    /// PWM at temperature; the first or last PWM outside the curve
+    pub fn pwm(&self, temp: f32) -> u8 {
+        let pos = match self.points.iter().position(|&(t, _)| t >= temp) {
+            Some(pos) => pos,
+            None => return self.points[self.points.len() - 1].1,
+        };
+        let (t1, p1) = self.points[pos];
+        if pos == 0 {
+            return p1;
+        }
+        let (t0, p0) = self.points[pos - 1];
+        let ratio = (temp - t0) / (t1 - t0);
+        (p0 as f32 + (p1 as f32 - p0 as f32) * ratio).round() as u8
+    }
+}
+
+/// `<temp>:<pwm>,...`, e.g. `30:60,45:150,55:255`
+impl FromStr for Curve {
+    type Err = anyhow::Error;
+    fn from_str(src: &str) -> std::result::Result<Self, Self::Err> {
+        let points = src
+            .split(',')
+            .map(|point| {
+                let mut parts = point.splitn(2, ':');
+                let temp = parts.next().unwrap_or_default().trim().parse::<f32>();
+                let pwm = parts.next().unwrap_or_default().trim().parse::<u8>();
+                match (temp, pwm) {
+                    (Ok(temp), Ok(pwm)) => Ok((temp, pwm)),
+                    _ => Err(anyhow!(
+                        "invalid curve point {}, expected <temp>:<pwm>",
+                        point
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(points)
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (temp, pwm)) in self.points.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}:{}", temp, pwm)?;
+        }
+        Ok(())
+    }
+}
+
+/// limits on how the PWM of a fan follows its target; all off by default
+#[derive(Debug, Clone, Default)]
+pub struct Smoothing {
+    /// ℃ the temperature must fall below a level before PWM is lowered
+    pub hysteresis: f32,
+    /// max PWM rise per second
+    pub ramp_up: Option<f32>,
+    /// max PWM fall per second
+    pub ramp_down: Option<f32>,
+    /// minimal time a PWM is held before it is lowered; rises are never delayed
+    pub dwell: Duration,
+    /// stop the fan below this temperature, until it gets hysteresis warmer
+    pub zero_below: Option<f32>,
+}
+
+/// PWM of a fan over time, following a temperature→PWM mapping with smoothing
+pub struct Governor {
+    smoothing: Smoothing,
+    /// (pwm, when it was last changed, when it was last updated)
+    last: Option<(u8, Instant, Instant)>,
+}
+
+impl Governor {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            last: None,
+        }
+    }
+
+    /// forget the last PWM, e.g. after PWM was set by others
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// next PWM at temperature
+    pub fn next<F: Fn(f32) -> u8>(&mut self, map: F, temp: f32, now: Instant) -> u8 {
+        let s = &self.smoothing;
+        let (current, changed, updated) = match self.last {
+            Some(v) => v,
+            None => {
+                let pwm = match s.zero_below {
+                    Some(zero) if temp < zero => 0,
+                    _ => map(temp),
+                };
+                self.last = Some((pwm, now, now));
+                return pwm;
+            }
+        };
+        // hysteresis: rise with the temperature, fall only if it would fall even when warmer
+        let rising = map(temp);
+        let falling = map(temp + s.hysteresis);
+        let mut target = if rising > current {
+            rising
+        } else if falling < current {
+            falling
+        } else {
+            current
+        };
+        if let Some(zero) = s.zero_below {
+            let stopped = current == 0;
+            if temp < zero || (stopped && temp < zero + s.hysteresis) {
+                target = 0;
+            }
+        }
+        if target < current && now.duration_since(changed) < s.dwell {
+            target = current;
+        }
+        let secs = now.duration_since(updated).as_secs_f32();
+        // a stopped fan starts at once, ramping from 0 would stall it
+        if target > current && current > 0 {
+            if let Some(rate) = s.ramp_up {
+                let step = (rate * secs).max(1.0);
+                target = target.min((current as f32 + step).min(255.0) as u8);
+            }
+        }
+        if target < current {
+            if let Some(rate) = s.ramp_down {
+                let step = (rate * secs).max(1.0);
+                target = target.max((current as f32 - step).max(0.0) as u8);
+            }
+        }
+        let changed = if target != current { now } else { changed };
+        self.last = Some((target, changed, now));
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// steps of 50 PWM every 5 ℃ from 30 ℃
+    fn step(temp: f32) -> u8 {
+        (((temp - 30.0) / 5.0).floor().max(0.0) * 50.0).min(255.0) as u8
+    }
+
+    fn run(governor: &mut Governor, temps: &[f32], interval: Duration) -> Vec<u8> {
+        let mut now = Instant::now();
+        temps
+            .iter()
+            .map(|&temp| {
+                now += interval;
+                governor.next(step, temp, now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn curve_interpolate() {
+        let curve: Curve = "30:60, 40:100,50:255".parse().unwrap();
+        assert_eq!(curve.pwm(20.0), 60);
+        assert_eq!(curve.pwm(35.0), 80);
+        assert_eq!(curve.pwm(40.0), 100);
+        assert_eq!(curve.pwm(60.0), 255);
+        assert_eq!(curve.to_string(), "30:60,40:100,50:255");
+        assert!("40:100,30:60".parse::<Curve>().is_err());
+        assert!("30:100,40:60".parse::<Curve>().is_err());
+        assert!("30:300".parse::<Curve>().is_err());
+        assert!("".parse::<Curve>().is_err());
+    }
+
+    #[test]
+    fn hysteresis_stops_hunting() {
+        let temps = [40.2, 39.8, 40.1, 39.9, 38.5, 37.9];
+        let mut plain = Governor::new(Smoothing::default());
+        let res = run(&mut plain, &temps, Duration::from_secs(5));
+        assert_eq!(res, vec![100, 50, 100, 50, 50, 50]);
+        let mut governor = Governor::new(Smoothing {
+            hysteresis: 2.0,
+            ..Default::default()
+        });
+        let res = run(&mut governor, &temps, Duration::from_secs(5));
+        assert_eq!(res, vec![100, 100, 100, 100, 100, 50]);
+    }
+
+    #[test]
+    fn ramp_limits() {
+        let mut governor = Governor::new(Smoothing {
+            ramp_up: Some(4.0),
+            ramp_down: Some(2.0),
+            ..Default::default()
+        });
+        let temps = [30.0, 50.0, 50.0, 30.0, 30.0, 60.0, 60.0];
+        let res = run(&mut governor, &temps, Duration::from_secs(5));
+        // a stopped fan starts at once
+        assert_eq!(res, vec![0, 200, 200, 190, 180, 200, 220]);
+    }
+
+    #[test]
+    fn dwell_before_lowering() {
+        let mut governor = Governor::new(Smoothing {
+            dwell: Duration::from_secs(12),
+            ..Default::default()
+        });
+        let temps = [40.0, 36.0, 36.0, 36.0, 46.0, 36.0];
+        let res = run(&mut governor, &temps, Duration::from_secs(5));
+        assert_eq!(res, vec![100, 100, 100, 50, 150, 150]);
+    }
+
+    #[test]
+    fn zero_rpm() {
+        let mut governor = Governor::new(Smoothing {
+            hysteresis: 3.0,
+            zero_below: Some(36.0),
+            ..Default::default()
+        });
+        let temps = [35.0, 37.0, 39.5, 40.0, 36.5, 35.5];
+        let res = run(&mut governor, &temps, Duration::from_secs(5));
+        assert_eq!(res, vec![0, 0, 50, 100, 50, 0]);
+    }
+}
@@ -0,0 +1,160 @@
+use super::calibration::Calibration;
+use anyhow::Result;
+use qute_ctrl::{platform::Platform, FanControl};
+use std::{collections::HashMap, fmt};
+
+/// PWM considered as the stall floor if the fan is not calibrated
+const DEFAULT_STALL_PWM: u8 = 0x40;
+
+/// PWM above the stall floor, at which the fan must be spinning
+const STALL_MARGIN: u8 = 15;
+
+/// a fan spinning slower than this ratio of the expected RPM is degraded
+const DEGRADED_RATIO: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FanHealth {
+    Healthy,
+    /// spins, but slower than expected, or reported NG by EC
+    Degraded,
+    /// does not spin while PWM is well above its stall floor
+    Stalled,
+    /// reported NG by EC and does not spin at all
+    Missing,
+}
+
+impl FanHealth {
+    /// the fan does not cool anymore
+    pub fn is_failed(&self) -> bool {
+        matches!(self, FanHealth::Stalled | FanHealth::Missing)
+    }
+}
+
+impl fmt::Display for FanHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FanHealth::Healthy => write!(f, "healthy"),
+            FanHealth::Degraded => write!(f, "degraded"),
+            FanHealth::Stalled => write!(f, "stalled"),
+            FanHealth::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// evaluate fan health from fan status, speed and PWM
+#[derive(Default)]
+pub struct Evaluator {
+    cals: Vec<Calibration>,
+    /// last (pwm, rpm) of each fan
+    last: HashMap<u8, (u8, u16)>,
+}
+
+impl Evaluator {
+    pub fn new(cals: Vec<Calibration>) -> Self {
+        Self {
+            cals,
+            last: HashMap::new(),
+        }
+    }
+
+    pub fn evaluate(&mut self, chip: &Platform, fan: u8) -> Result<FanHealth> {
+        let status_ok = chip.get_fan_status(fan)? == "OK";
+        let rpm = chip.get_fan_speed(fan)?;
+        let pwm = chip.get_fan_pwm(fan)?;
+        let cal = self.cals.iter().find(|v| v.fan == fan);
+        let mut health = classify(status_ok, pwm, rpm, cal);
+        // RPM collapses while PWM unchanged
+        if let Some(&(last_pwm, last_rpm)) = self.last.get(&fan) {
+            let collapsed = (rpm as f32) < last_rpm as f32 * DEGRADED_RATIO;
+            if health == FanHealth::Healthy && last_pwm == pwm && collapsed {
+                health = FanHealth::Degraded;
+            }
+        }
+        trace!("fan {}: pwm {}, {} RPM, {}", fan, pwm, rpm, health);
+        self.last.insert(fan, (pwm, rpm));
+        Ok(health)
+    }
+
+    /// (pwm, rpm) of fan read by the last evaluation
+    pub fn reading(&self, fan: u8) -> Option<(u8, u16)> {
+        self.last.get(&fan).cloned()
+    }
+}
+
+pub fn classify(status_ok: bool, pwm: u8, rpm: u16, cal: Option<&Calibration>) -> FanHealth {
+    if rpm == 0 {
+        if !status_ok {
+            return FanHealth::Missing;
+        }
+        let floor = cal.map(|v| v.start_pwm).unwrap_or(DEFAULT_STALL_PWM);
+        if pwm > floor.saturating_add(STALL_MARGIN) {
+            return FanHealth::Stalled;
+        }
+        return FanHealth::Healthy;
+    }
+    if !status_ok {
+        return FanHealth::Degraded;
+    }
+    if let Some(expected) = cal.and_then(|v| v.expected_rpm(pwm)) {
+        if (rpm as f32) < expected as f32 * DEGRADED_RATIO {
+            return FanHealth::Degraded;
+        }
+    }
+    FanHealth::Healthy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calibration() -> Calibration {
+        Calibration {
+            fan: 0,
+            curve: vec![(0, 0), (60, 0), (75, 500), (150, 1000), (255, 1700)],
+            start_pwm: 72,
+            sustain_pwm: 66,
+            max_rpm: 1700,
+        }
+    }
+
+    #[test]
+    fn classify_missing() {
+        assert_eq!(classify(false, 200, 0, None), FanHealth::Missing);
+    }
+
+    #[test]
+    fn classify_stalled() {
+        assert_eq!(classify(true, 200, 0, None), FanHealth::Stalled);
+        assert_eq!(
+            classify(true, 100, 0, Some(&calibration())),
+            FanHealth::Stalled
+        );
+    }
+
+    #[test]
+    fn classify_stopped_below_floor() {
+        assert_eq!(classify(true, 0, 0, None), FanHealth::Healthy);
+        assert_eq!(
+            classify(true, 80, 0, Some(&calibration())),
+            FanHealth::Healthy
+        );
+    }
+
+    #[test]
+    fn classify_degraded() {
+        assert_eq!(classify(false, 200, 1200, None), FanHealth::Degraded);
+        assert_eq!(
+            classify(true, 150, 500, Some(&calibration())),
+            FanHealth::Degraded
+        );
+    }
+
+    #[test]
+    fn classify_healthy() {
+        assert_eq!(
+            classify(true, 150, 950, Some(&calibration())),
+            FanHealth::Healthy
+        );
+        assert_eq!(classify(true, 150, 500, None), FanHealth::Healthy);
+    }
+}
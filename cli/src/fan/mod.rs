@@ -0,0 +1,16 @@
+pub mod calibration;
+pub mod curve;
+pub mod health;
+pub mod pid;
+pub mod wear;
+
+use anyhow::Result;
+use qute_ctrl::{platform::Platform, FanControl, FanMode};
+
+/// hand every fan of the platform back to EC fw, not only configured ones, so that none will
+/// stay at the last PWM
+pub fn hand_back(chip: &Platform) -> Result<()> {
+    chip.hand_back_fans()?;
+    println!("√ mode of all fans was set to {}", FanMode::Auto);
+    Ok(())
+}
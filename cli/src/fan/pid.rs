@@ -0,0 +1,151 @@
+use std::time::Instant;
+
+/// parameters of the pid method
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidParams {
+    /// temperature to hold, in ℃
+    pub setpoint: f32,
+    /// PWM per ℃ above the setpoint
+    pub kp: f32,
+    /// PWM per ℃·s above the setpoint
+    pub ki: f32,
+    /// PWM per ℃/s of temperature rise
+    pub kd: f32,
+}
+
+impl Default for PidParams {
+    fn default() -> Self {
+        Self {
+            setpoint: 40.0,
+            kp: 10.0,
+            ki: 0.5,
+            kd: 0.0,
+        }
+    }
+}
+
+/// PID loop holding the temperature at the setpoint
+pub struct Pid {
+    params: PidParams,
+    min_pwm: u8,
+    max_pwm: u8,
+    /// integral term, in PWM
+    integral: f32,
+    /// (temperature, when) of the last update
+    last: Option<(f32, Instant)>,
+}
+
+impl Pid {
+    /// output is clamped to `min_pwm`-`max_pwm`
+    pub fn new(params: PidParams, min_pwm: u8, max_pwm: u8) -> Self {
+        Self {
+            params,
+            min_pwm,
+            max_pwm,
+            integral: min_pwm as f32,
+            last: None,
+        }
+    }
+
+    /// start over, e.g. after PWM was set by others
+    pub fn reset(&mut self) {
+        self.integral = self.min_pwm as f32;
+        self.last = None;
+    }
+
+    /// next PWM at temperature
+    pub fn next(&mut self, temp: f32, now: Instant) -> u8 {
+        let p = &self.params;
+        let (min, max) = (self.min_pwm as f32, self.max_pwm as f32);
+        let error = temp - p.setpoint;
+        // derivative of the temperature rather than of the error, so that the setpoint
+        // does not kick
+        let (dt, derivative) = match self.last {
+            Some((last, when)) => {
+                let dt = now.duration_since(when).as_secs_f32();
+                if dt > 0.0 {
+                    (dt, (temp - last) / dt)
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            None => (0.0, 0.0),
+        };
+        self.last = Some((temp, now));
+        let pd = p.kp * error + p.kd * derivative;
+        let step = p.ki * error * dt;
+        // anti-windup: integrate only until the output saturates
+        let integral = if step > 0.0 {
+            (self.integral + step).min((max - pd).max(self.integral))
+        } else {
+            (self.integral + step).max((min - pd).min(self.integral))
+        };
+        self.integral = integral.max(min).min(max);
+        let output = pd + self.integral;
+        trace!(
+            "pid: error {:.1} ℃, integral {:.1}, derivative {:.2} ℃/s",
+            error,
+            self.integral,
+            derivative
+        );
+        output.max(min).min(max).round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn run(pid: &mut Pid, temps: &[f32]) -> Vec<u8> {
+        let mut now = Instant::now();
+        temps
+            .iter()
+            .map(|&temp| {
+                now += Duration::from_secs(5);
+                pid.next(temp, now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn proportional_and_clamp() {
+        let params = PidParams {
+            ki: 0.0,
+            ..Default::default()
+        };
+        let mut pid = Pid::new(params, 60, 230);
+        assert_eq!(run(&mut pid, &[45.0, 30.0, 80.0]), vec![110, 60, 230]);
+    }
+
+    #[test]
+    fn integral_removes_offset() {
+        let mut pid = Pid::new(PidParams::default(), 0, 255);
+        let res = run(&mut pid, &[42.0, 42.0, 42.0, 42.0]);
+        assert_eq!(res, vec![20, 25, 30, 35]);
+        // holds the PWM reached at the setpoint
+        assert_eq!(run(&mut pid, &[40.0, 40.0]), vec![15, 15]);
+    }
+
+    #[test]
+    fn anti_windup() {
+        let mut pid = Pid::new(PidParams::default(), 0, 255);
+        let mut temps = vec![60.0; 100];
+        temps.push(39.0);
+        let res = run(&mut pid, &temps);
+        assert_eq!(res[99], 255);
+        // recovers at once when cooled down, rather than unwinding a huge integral
+        assert!(res[100] < 100, "{:?}", res[100]);
+    }
+
+    #[test]
+    fn derivative_on_rise() {
+        let params = PidParams {
+            ki: 0.0,
+            kd: 50.0,
+            ..Default::default()
+        };
+        let mut pid = Pid::new(params, 0, 255);
+        assert_eq!(run(&mut pid, &[40.0, 41.0, 41.0]), vec![0, 20, 10]);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::state;
+use anyhow::Result;
+use chrono::prelude::*;
+use json::JsonValue;
+use std::{collections::HashMap, fmt};
+
+/// name of the state file
+const STATE_FILE: &str = "fan-wear.json";
+
+/// PWM range of a bucket
+const BUCKET_SIZE: u8 = 16;
+
+/// samples to establish the baseline RPM of a bucket
+const BASELINE_SAMPLES: u32 = 120;
+
+/// weight of a new sample in the recent RPM
+const RECENT_WEIGHT: f32 = 0.02;
+
+/// days of daily averages kept for the trend
+const HISTORY_DAYS: usize = 365;
+
+/// a fan spinning this ratio slower than baseline at the same PWM is worn
+const RPM_DROP_ALERT: f32 = 0.15;
+
+/// a fan losing this ratio of its maximum RPM is worn
+const MAX_RPM_DROP_ALERT: f32 = 0.1;
+
+/// sign of fan wear
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub fan: u8,
+    /// lower bound of the PWM range
+    pub pwm: u8,
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fan {}: {}", self.fan, self.message)
+    }
+}
+
+/// RPM of a fan within a PWM range
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    /// lower bound of the PWM range
+    pub pwm: u8,
+    /// average RPM when the fan was new
+    pub baseline: f32,
+    pub baseline_samples: u32,
+    /// moving average of RPM
+    pub recent: f32,
+    /// (date, average RPM, samples) of each day
+    pub daily: Vec<(String, f32, u32)>,
+}
+
+impl Bucket {
+    fn new(pwm: u8) -> Self {
+        Self {
+            pwm,
+            baseline: 0.0,
+            baseline_samples: 0,
+            recent: 0.0,
+            daily: vec![],
+        }
+    }
+
+    fn record(&mut self, rpm: u16, day: &str) {
+        let rpm = rpm as f32;
+        if self.baseline_samples < BASELINE_SAMPLES {
+            let n = self.baseline_samples as f32;
+            self.baseline = (self.baseline * n + rpm) / (n + 1.0);
+            self.baseline_samples += 1;
+            self.recent = self.baseline;
+        } else {
+            self.recent += (rpm - self.recent) * RECENT_WEIGHT;
+        }
+        match self.daily.last_mut() {
+            Some((d, avg, n)) if d == day => {
+                *avg = (*avg * *n as f32 + rpm) / (*n + 1) as f32;
+                *n += 1;
+            }
+            _ => {
+                self.daily.push((day.to_owned(), rpm, 1));
+                if self.daily.len() > HISTORY_DAYS {
+                    self.daily.remove(0);
+                }
+            }
+        }
+    }
+
+    pub fn has_baseline(&self) -> bool {
+        self.baseline_samples >= BASELINE_SAMPLES
+    }
+
+    /// ratio of RPM lost since baseline
+    pub fn drop_ratio(&self) -> f32 {
+        if !self.has_baseline() || self.baseline <= 0.0 {
+            return 0.0;
+        }
+        (self.baseline - self.recent) / self.baseline
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let daily: Vec<JsonValue> = self
+            .daily
+            .iter()
+            .map(|(day, rpm, n)| json::array![day.as_str(), *rpm, *n])
+            .collect();
+        json::object! {
+            pwm: self.pwm,
+            baseline: self.baseline,
+            baseline_samples: self.baseline_samples,
+            recent: self.recent,
+            daily: daily,
+        }
+    }
+
+    fn from_json(obj: &JsonValue) -> Option<Self> {
+        let daily = obj["daily"]
+            .members()
+            .map(|v| Some((v[0].as_str()?.to_owned(), v[1].as_f32()?, v[2].as_u32()?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            pwm: obj["pwm"].as_u8()?,
+            baseline: obj["baseline"].as_f32()?,
+            baseline_samples: obj["baseline_samples"].as_u32()?,
+            recent: obj["recent"].as_f32()?,
+            daily,
+        })
+    }
+}
+
+/// RPM-at-PWM history of a fan
+#[derive(Debug, Clone)]
+pub struct FanWear {
+    pub fan: u8,
+    /// ordered by pwm
+    pub buckets: Vec<Bucket>,
+}
+
+impl FanWear {
+    fn record(&mut self, pwm: u8, rpm: u16, day: &str) {
+        let lower = pwm - pwm % BUCKET_SIZE;
+        let pos = match self.buckets.iter().position(|v| v.pwm >= lower) {
+            Some(pos) if self.buckets[pos].pwm == lower => pos,
+            Some(pos) => {
+                self.buckets.insert(pos, Bucket::new(lower));
+                pos
+            }
+            None => {
+                self.buckets.push(Bucket::new(lower));
+                self.buckets.len() - 1
+            }
+        };
+        self.buckets[pos].record(rpm, day);
+    }
+
+    /// signs of wear, empty if the fan is fine
+    pub fn alerts(&self) -> Vec<Alert> {
+        let mut res = vec![];
+        // the top bucket tells the maximum RPM
+        let top = self
+            .buckets
+            .last()
+            .filter(|v| v.pwm >= 0xff - BUCKET_SIZE)
+            .map(|v| v.pwm);
+        for bucket in self.buckets.iter() {
+            let ratio = bucket.drop_ratio();
+            let message = if top == Some(bucket.pwm) {
+                if ratio < MAX_RPM_DROP_ALERT {
+                    continue;
+                }
+                format!(
+                    "maximum speed dropped to {:.0} RPM, was {:.0} RPM ({:.0}%)",
+                    bucket.recent,
+                    bucket.baseline,
+                    -ratio * 100.0
+                )
+            } else {
+                if ratio < RPM_DROP_ALERT {
+                    continue;
+                }
+                format!(
+                    "needs more PWM for the same speed, {:.0} RPM at pwm {}-{}, was {:.0} RPM ({:.0}%)",
+                    bucket.recent,
+                    bucket.pwm,
+                    bucket.pwm as u16 + BUCKET_SIZE as u16 - 1,
+                    bucket.baseline,
+                    -ratio * 100.0
+                )
+            };
+            res.push(Alert {
+                fan: self.fan,
+                pwm: bucket.pwm,
+                message,
+            });
+        }
+        res
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let buckets: Vec<JsonValue> = self.buckets.iter().map(|v| v.to_json()).collect();
+        json::object! {
+            fan: self.fan,
+            buckets: buckets,
+        }
+    }
+
+    fn from_json(obj: &JsonValue) -> Option<Self> {
+        let buckets = obj["buckets"]
+            .members()
+            .map(Bucket::from_json)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            fan: obj["fan"].as_u8()?,
+            buckets,
+        })
+    }
+}
+
+/// record RPM-at-PWM of fans, kept in the state file
+#[derive(Default)]
+pub struct Tracker {
+    fans: Vec<FanWear>,
+    /// last pwm of each fan; RPM settles only if PWM unchanged
+    last_pwm: HashMap<u8, u8>,
+    /// (fan, pwm) of alerts reported
+    alerted: Vec<(u8, u8)>,
+}
+
+impl Tracker {
+    pub fn load() -> Result<Self> {
+        let fans = match state::load(STATE_FILE)? {
+            Some(obj) => obj["fans"]
+                .members()
+                .map(|v| {
+                    FanWear::from_json(v).ok_or_else(|| {
+                        anyhow!("invalid state file {}", state::path(STATE_FILE).display())
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![],
+        };
+        Ok(Self {
+            fans,
+            ..Default::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let fans: Vec<JsonValue> = self.fans.iter().map(|v| v.to_json()).collect();
+        state::save(STATE_FILE, &json::object! { fans: fans })
+    }
+
+    pub fn get(&self, fan: u8) -> Option<&FanWear> {
+        self.fans.iter().find(|v| v.fan == fan)
+    }
+
+    /// record speed of a running fan; return new alerts
+    pub fn record(&mut self, fan: u8, pwm: u8, rpm: u16) -> Vec<Alert> {
+        let settled = self.last_pwm.insert(fan, pwm) == Some(pwm);
+        if !settled || rpm == 0 {
+            return vec![];
+        }
+        let day = Local::now().format("%Y-%m-%d").to_string();
+        let wear = match self.fans.iter().position(|v| v.fan == fan) {
+            Some(pos) => &mut self.fans[pos],
+            None => {
+                self.fans.push(FanWear {
+                    fan,
+                    buckets: vec![],
+                });
+                self.fans.last_mut().unwrap()
+            }
+        };
+        wear.record(pwm, rpm, &day);
+        let alerts = wear.alerts();
+        self.alerted.retain(|&(f, _)| f != fan);
+        let mut res = vec![];
+        for alert in alerts {
+            let key = (alert.fan, alert.pwm);
+            if !self.alerted.contains(&key) {
+                res.push(alert);
+            }
+            self.alerted.push(key);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_n(wear: &mut FanWear, pwm: u8, rpm: u16, n: u32) {
+        for _ in 0..n {
+            wear.record(pwm, rpm, "2026-10-19");
+        }
+    }
+
+    #[test]
+    fn no_alert_while_healthy() {
+        let mut wear = FanWear {
+            fan: 0,
+            buckets: vec![],
+        };
+        record_n(&mut wear, 128, 1000, BASELINE_SAMPLES);
+        record_n(&mut wear, 250, 1700, BASELINE_SAMPLES);
+        record_n(&mut wear, 128, 980, 500);
+        assert_eq!(wear.buckets.len(), 2);
+        assert!(wear.alerts().is_empty());
+    }
+
+    #[test]
+    fn alert_on_rpm_drop() {
+        let mut wear = FanWear {
+            fan: 0,
+            buckets: vec![],
+        };
+        record_n(&mut wear, 128, 1000, BASELINE_SAMPLES);
+        record_n(&mut wear, 130, 800, 500);
+        let alerts = wear.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].message.contains("needs more PWM"));
+    }
+
+    #[test]
+    fn alert_on_max_rpm_drop() {
+        let mut wear = FanWear {
+            fan: 0,
+            buckets: vec![],
+        };
+        record_n(&mut wear, 255, 1700, BASELINE_SAMPLES);
+        record_n(&mut wear, 255, 1450, 500);
+        let alerts = wear.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].message.contains("maximum speed"));
+    }
+
+    #[test]
+    fn daily_average() {
+        let mut bucket = Bucket::new(0);
+        bucket.record(1000, "2026-10-18");
+        bucket.record(1200, "2026-10-18");
+        bucket.record(900, "2026-10-19");
+        assert_eq!(bucket.daily.len(), 2);
+        assert_eq!(bucket.daily[0], ("2026-10-18".to_owned(), 1100.0, 2));
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use qute_ctrl::{LedColor, LedControl, LedMode, LedState, StatusLed, SwitchState};
+use std::{fmt, str::FromStr};
+
+/// a change of LEDs:
+/// - `off`, `green`, `red`: the status LED
+/// - `blink-green`, `blink-red`, `blink-auto`: blink the status LED, auto alternates colors
+/// - `brightness:<0-255>`: brightness of front LEDs
+/// - `10g:<on|off>`: the 10G NIC present LED
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Status(StatusLed),
+    Brightness(u8),
+    Nic10g(bool),
+}
+
+impl Action {
+    pub fn apply<T: LedControl>(&self, chip: &T) -> Result<()> {
+        match *self {
+            Action::Status(StatusLed { mode, color }) => match mode {
+                LedMode::Off => chip.set_status_led(color, false)?,
+                LedMode::On => chip.set_status_led(color, true)?,
+                LedMode::Blink => chip.blink_status_led(color, true)?,
+            },
+            Action::Brightness(v) => chip.set_led_by_pwm(v)?,
+            Action::Nic10g(v) => chip.set_10G_led(v)?,
+        }
+        Ok(())
+    }
+
+    /// actions on the same LED have the same target
+    pub fn target(&self) -> u8 {
+        match self {
+            Action::Status(_) => 0,
+            Action::Brightness(_) => 1,
+            Action::Nic10g(_) => 2,
+        }
+    }
+
+    /// apply to a captured LED configuration
+    pub fn apply_to(&self, state: &mut LedState) {
+        match *self {
+            Action::Status(v) => state.status = v,
+            Action::Brightness(v) => state.brightness = v,
+            Action::Nic10g(v) => state.nic_10g = v,
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        if let Some(v) = t.strip_prefix("brightness:") {
+            let v = v
+                .parse()
+                .map_err(|_| anyhow!("invalid brightness {}, must be 0-255", v))?;
+            return Ok(Action::Brightness(v));
+        }
+        if let Some(v) = t.strip_prefix("10g:") {
+            let v: SwitchState = v.parse()?;
+            return Ok(Action::Nic10g(v.is_on()));
+        }
+        let status = |mode, color| Ok(Action::Status(StatusLed { mode, color }));
+        match t.as_str() {
+            "off" => status(LedMode::Off, LedColor::Green),
+            "green" => status(LedMode::On, LedColor::Green),
+            "red" => status(LedMode::On, LedColor::Red),
+            "blink-green" => status(LedMode::Blink, LedColor::Green),
+            "blink-red" => status(LedMode::Blink, LedColor::Red),
+            "blink-auto" => status(LedMode::Blink, LedColor::Auto),
+            _ => Err(anyhow!("invalid LED action: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Status(StatusLed { mode, color }) => match mode {
+                LedMode::Off => write!(f, "off"),
+                LedMode::On => write!(f, "{}", color),
+                LedMode::Blink => write!(f, "blink-{}", color),
+            },
+            Action::Brightness(v) => write!(f, "brightness:{}", v),
+            Action::Nic10g(v) => write!(f, "10g:{}", SwitchState::from(*v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for spec in &[
+            "off",
+            "green",
+            "red",
+            "blink-auto",
+            "brightness:40",
+            "10g:off",
+        ] {
+            let action: Action = spec.parse().unwrap();
+            assert_eq!(action.to_string(), *spec);
+        }
+        assert!("brightness:256".parse::<Action>().is_err());
+        assert!("blue".parse::<Action>().is_err());
+    }
+}
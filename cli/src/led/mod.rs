@@ -0,0 +1,4 @@
+pub mod action;
+pub mod pattern;
+pub mod rules;
+pub mod schedule;
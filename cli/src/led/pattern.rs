@@ -0,0 +1,195 @@
+use super::action::Action;
+use crate::utils;
+use anyhow::{Context, Result};
+use qute_ctrl::{LedColor, LedControl, LedMode, StatusLed};
+use std::{
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// on and off time of a flash of blink codes
+const CODE_FLASH: Duration = Duration::from_millis(300);
+
+/// pause between repeats of blink codes
+const CODE_PAUSE: Duration = Duration::from_millis(1500);
+
+/// steps of a breath, up and down
+const BREATH_STEPS: u32 = 40;
+
+/// a sequence of LED actions over time:
+/// - `alternate[:<ms>]`: alternating green and red, default 500 ms each
+/// - `code:<n>[:<color>]`: blink code, n flashes then a pause, default red
+/// - `breathe[:<ms>]`: brightness rises and falls, default period 4000 ms
+/// - `<action>@<ms>,...`: custom steps, e.g. `green@500,off@200,brightness:40@1000`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub steps: Vec<(Action, Duration)>,
+}
+
+impl Pattern {
+    fn alternate(time: Duration) -> Self {
+        let status = |color| {
+            Action::Status(StatusLed {
+                mode: LedMode::On,
+                color,
+            })
+        };
+        Self {
+            steps: vec![
+                (status(LedColor::Green), time),
+                (status(LedColor::Red), time),
+            ],
+        }
+    }
+
+    fn code(n: u32, color: LedColor) -> Self {
+        let on = Action::Status(StatusLed {
+            mode: LedMode::On,
+            color,
+        });
+        let off = Action::Status(StatusLed {
+            mode: LedMode::Off,
+            color,
+        });
+        let mut steps = vec![];
+        for _ in 0..n {
+            steps.push((on, CODE_FLASH));
+            steps.push((off, CODE_FLASH));
+        }
+        if let Some(last) = steps.last_mut() {
+            last.1 = CODE_PAUSE;
+        }
+        Self { steps }
+    }
+
+    fn breathe(period: Duration) -> Self {
+        let step = period / BREATH_STEPS;
+        let half = BREATH_STEPS / 2;
+        let steps = (0..BREATH_STEPS)
+            .map(|i| {
+                let level = if i <= half { i } else { BREATH_STEPS - i };
+                let value = (level * 255 / half) as u8;
+                (Action::Brightness(value), step)
+            })
+            .collect();
+        Self { steps }
+    }
+
+    /// play the pattern for times, forever if None, until terminated; LEDs are restored after
+    pub fn play<T: LedControl>(&self, chip: &T, times: Option<u32>) -> Result<()> {
+        let state = chip.get_led_state()?;
+        let res = self.play_steps(chip, times);
+        chip.set_led_state(&state)?;
+        res
+    }
+
+    fn play_steps<T: LedControl>(&self, chip: &T, times: Option<u32>) -> Result<()> {
+        let mut played = 0;
+        while times.map(|v| played < v).unwrap_or(true) {
+            for (action, time) in self.steps.iter() {
+                if utils::is_terminated() {
+                    return Ok(());
+                }
+                let start = Instant::now();
+                action.apply(chip)?;
+                if let Some(left) = time.checked_sub(start.elapsed()) {
+                    sleep(left);
+                }
+            }
+            played += 1;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        let mut parts = t.split(':');
+        let name = parts.next().unwrap_or_default();
+        let ms = |v: Option<&str>, default: u64| -> Result<Duration> {
+            let ms = match v {
+                Some(v) => v.parse().with_context(|| format!("invalid time {}", v))?,
+                None => default,
+            };
+            Ok(Duration::from_millis(ms))
+        };
+        let pattern = match name {
+            "alternate" => Pattern::alternate(ms(parts.next(), 500)?),
+            "breathe" => Pattern::breathe(ms(parts.next(), 4000)?),
+            "code" => {
+                let n: u32 = parts
+                    .next()
+                    .ok_or_else(|| anyhow!("blink code requires a number"))?
+                    .parse()
+                    .with_context(|| "invalid blink code")?;
+                let color = match parts.next() {
+                    Some(v) => v.parse()?,
+                    None => LedColor::Red,
+                };
+                Pattern::code(n, color)
+            }
+            _ => {
+                let steps = t
+                    .split(',')
+                    .map(|step| {
+                        let mut parts = step.splitn(2, '@');
+                        let action: Action = parts.next().unwrap_or_default().parse()?;
+                        let time = parts
+                            .next()
+                            .ok_or_else(|| anyhow!("step {} requires a time, e.g. @500", step))?;
+                        Ok((action, ms(Some(time), 0)?))
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("invalid LED pattern: {}", s))?;
+                Pattern { steps }
+            }
+        };
+        if pattern.steps.is_empty() {
+            return Err(anyhow!("empty LED pattern: {}", s));
+        }
+        Ok(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_builtin() {
+        let p: Pattern = "alternate".parse().unwrap();
+        assert_eq!(p.steps.len(), 2);
+        assert_eq!(p.steps[0].1, Duration::from_millis(500));
+        let p: Pattern = "code:3".parse().unwrap();
+        assert_eq!(p.steps.len(), 6);
+        assert_eq!(p.steps[5].1, CODE_PAUSE);
+        let p: Pattern = "code:2:green".parse().unwrap();
+        assert_eq!(p.steps[0].0.to_string(), "green");
+        assert!("code:0".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn breathe() {
+        let p: Pattern = "breathe:2000".parse().unwrap();
+        assert_eq!(p.steps.len(), BREATH_STEPS as usize);
+        assert_eq!(p.steps[0].0, Action::Brightness(0));
+        assert_eq!(p.steps[20].0, Action::Brightness(255));
+        assert_eq!(p.steps[39].0, Action::Brightness(12));
+        assert_eq!(p.steps[0].1, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn parse_custom() {
+        let p: Pattern = "green@500, off@200,brightness:40@1000".parse().unwrap();
+        assert_eq!(p.steps.len(), 3);
+        assert_eq!(
+            p.steps[2],
+            (Action::Brightness(40), Duration::from_millis(1000))
+        );
+        assert!("green".parse::<Pattern>().is_err());
+        assert!("green@soon".parse::<Pattern>().is_err());
+    }
+}
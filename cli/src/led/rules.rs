@@ -0,0 +1,332 @@
+use super::action::Action;
+use crate::utils;
+use anyhow::{Context, Result};
+use qute_ctrl::{platform::Platform, FanControl, LedControl, SensorId, Temperature};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread::sleep,
+    time::Duration,
+};
+
+/// rules used if none given
+pub const DEFAULT_RULES: [&str; 4] = [
+    "md-degraded=blink-red",
+    "fan-ng=red",
+    "temp>60=blink-red",
+    "always=green",
+];
+
+/// system conditions, collected each cycle
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Facts {
+    /// names of degraded md arrays
+    pub md_degraded: Vec<String>,
+    /// carrier of network interfaces
+    pub links: HashMap<String, bool>,
+    /// fans reported NG by EC
+    pub fans_ng: Vec<u8>,
+    /// highest temperature of EC sensors, in ℃
+    pub max_temp: Option<f32>,
+}
+
+impl Facts {
+    pub fn collect(
+        chip: &Platform,
+        fans: u8,
+        sensors: &[SensorId],
+        procfs: &Path,
+        sysfs: &Path,
+    ) -> Result<Self> {
+        let mdstat = fs::read_to_string(procfs.join("mdstat")).unwrap_or_default();
+        let mut fans_ng = vec![];
+        for fan in 0..fans {
+            if chip.get_fan_status(fan)? != "OK" {
+                fans_ng.push(fan);
+            }
+        }
+        let mut max_temp: Option<f32> = None;
+        for &sensor in sensors {
+            if let Some(temp) = chip.get_sensor_temperature(sensor)? {
+                max_temp = Some(max_temp.map_or(temp, |v| v.max(temp)));
+            }
+        }
+        Ok(Self {
+            md_degraded: parse_mdstat(&mdstat),
+            links: read_carriers(sysfs)?,
+            fans_ng,
+            max_temp,
+        })
+    }
+}
+
+/// names of degraded md arrays: missing members, e.g. `[U_]`, or failed ones, e.g. `sdc1[2](F)`
+pub fn parse_mdstat(text: &str) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    let mut current: Option<String> = None;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        if line.starts_with("md") {
+            let name = fields.next().unwrap_or_default().to_owned();
+            if line.contains("(F)") {
+                res.push(name.clone());
+            }
+            current = Some(name);
+            continue;
+        }
+        let name = match current {
+            Some(ref v) => v,
+            None => continue,
+        };
+        let missing = fields
+            .filter(|v| v.starts_with('[') && v.ends_with(']'))
+            .any(|v| v.chars().all(|c| "[]U_".contains(c)) && v.contains('_'));
+        if missing && !res.contains(name) {
+            res.push(name.clone());
+        }
+    }
+    res
+}
+
+/// carrier of physical network interfaces, `<sysfs>/class/net/*/carrier`
+pub fn read_carriers(sysfs: &Path) -> Result<HashMap<String, bool>> {
+    let mut res = HashMap::new();
+    let dir = sysfs.join("class/net");
+    if !dir.exists() {
+        return Ok(res);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        // virtual interfaces, e.g. lo and bridges, have no device
+        if !path.join("device").exists() {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        // reading carrier of an interface which is down fails
+        let carrier = fs::read_to_string(path.join("carrier"))
+            .map(|v| v.trim() == "1")
+            .unwrap_or(false);
+        res.insert(name, carrier);
+    }
+    Ok(res)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// any md array degraded
+    MdDegraded,
+    /// any fan reported NG
+    FanNg,
+    /// highest temperature above, in ℃
+    TempAbove(f32),
+    /// the interface, or any physical interface, has no carrier
+    LinkDown(Option<String>),
+    Always,
+}
+
+impl Condition {
+    pub fn matches(&self, facts: &Facts) -> bool {
+        match self {
+            Condition::MdDegraded => !facts.md_degraded.is_empty(),
+            Condition::FanNg => !facts.fans_ng.is_empty(),
+            Condition::TempAbove(limit) => facts.max_temp.map(|v| v > *limit).unwrap_or(false),
+            Condition::LinkDown(Some(name)) => facts.links.get(name) == Some(&false),
+            Condition::LinkDown(None) => facts.links.values().any(|v| !v),
+            Condition::Always => true,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        if let Some(v) = t.strip_prefix("temp>") {
+            let limit = v
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid temperature {}", v))?;
+            return Ok(Condition::TempAbove(limit));
+        }
+        if let Some(v) = t.strip_prefix("link-down:") {
+            return Ok(Condition::LinkDown(Some(v.trim().to_owned())));
+        }
+        match t.as_str() {
+            "md-degraded" => Ok(Condition::MdDegraded),
+            "fan-ng" => Ok(Condition::FanNg),
+            "link-down" => Ok(Condition::LinkDown(None)),
+            "always" => Ok(Condition::Always),
+            _ => Err(anyhow!("invalid condition: {}", s)),
+        }
+    }
+}
+
+/// `<condition>=<action>`, e.g. `md-degraded=blink-red`, `link-down:eth2=10g:off`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub condition: Condition,
+    pub action: Action,
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, '=');
+        let condition = parts.next().unwrap_or_default().parse();
+        let action = parts
+            .next()
+            .ok_or_else(|| anyhow!("require an action, e.g. =blink-red"))
+            .and_then(|v| v.parse());
+        let parse = || -> Result<Rule> {
+            Ok(Rule {
+                condition: condition?,
+                action: action?,
+            })
+        };
+        parse().with_context(|| format!("invalid LED rule: {}", s))
+    }
+}
+
+/// actions of the first matching rule of each LED
+pub fn evaluate(rules: &[Rule], facts: &Facts) -> Vec<Action> {
+    let mut res: Vec<Action> = vec![];
+    for rule in rules {
+        let target = rule.action.target();
+        if res.iter().any(|v| v.target() == target) {
+            continue;
+        }
+        if rule.condition.matches(facts) {
+            res.push(rule.action);
+        }
+    }
+    res
+}
+
+/// evaluate rules periodically until terminated, then restore LEDs
+pub struct Engine {
+    pub rules: Vec<Rule>,
+    pub fans: u8,
+    pub interval: Duration,
+    pub procfs: PathBuf,
+    pub sysfs: PathBuf,
+}
+
+impl Engine {
+    pub fn run(&self, chip: &Platform) -> Result<()> {
+        let state = chip.get_led_state()?;
+        let sensors = chip.sensors()?;
+        let res = self.run_forever(chip, &sensors);
+        chip.set_led_state(&state)?;
+        res
+    }
+
+    fn run_forever(&self, chip: &Platform, sensors: &[SensorId]) -> Result<()> {
+        let mut applied: Vec<Action> = vec![];
+        while !utils::is_terminated() {
+            let facts = Facts::collect(chip, self.fans, sensors, &self.procfs, &self.sysfs)?;
+            trace!("{:?}", facts);
+            for action in evaluate(&self.rules, &facts) {
+                if applied.contains(&action) {
+                    continue;
+                }
+                action.apply(chip)?;
+                info!("LED: {}", action);
+                applied.retain(|v| v.target() != action.target());
+                applied.push(action);
+            }
+            sleep(self.interval);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qute_ctrl::testutil::TempDir;
+
+    const MDSTAT_CLEAN: &str = include_str!("../../tests/fixtures/mdstat-clean");
+    const MDSTAT_DEGRADED: &str = include_str!("../../tests/fixtures/mdstat-degraded");
+
+    fn rules(specs: &[&str]) -> Vec<Rule> {
+        specs.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn mdstat() {
+        assert!(parse_mdstat(MDSTAT_CLEAN).is_empty());
+        assert_eq!(parse_mdstat(MDSTAT_DEGRADED), vec!["md1", "md0"]);
+        assert!(parse_mdstat("").is_empty());
+    }
+
+    #[test]
+    fn carriers() {
+        let root = TempDir::new("rules-net");
+        for (name, carrier, device) in &[
+            ("eth0", "1\n", true),
+            ("eth2", "0\n", true),
+            ("lo", "1\n", false),
+        ] {
+            root.write(&format!("class/net/{}/carrier", name), carrier);
+            if *device {
+                fs::create_dir_all(root.join("class/net").join(name).join("device")).unwrap();
+            }
+        }
+        let links = read_carriers(&root).unwrap();
+        assert_eq!(links.len(), 2);
+        assert!(links["eth0"]);
+        assert!(!links["eth2"]);
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rules = rules(&DEFAULT_RULES);
+        assert_eq!(rules[2].condition, Condition::TempAbove(60.0));
+        let rule: Rule = "link-down:eth2=10g:off".parse().unwrap();
+        assert_eq!(
+            rule.condition,
+            Condition::LinkDown(Some(String::from("eth2")))
+        );
+        assert_eq!(rule.action, Action::Nic10g(false));
+        assert!("raid-ok=green".parse::<Rule>().is_err());
+        assert!("always".parse::<Rule>().is_err());
+        assert!("temp>hot=red".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn first_match_of_each_led() {
+        let rules = rules(&[
+            "md-degraded=blink-red",
+            "fan-ng=red",
+            "temp>60=blink-red",
+            "link-down=10g:off",
+            "always=green",
+            "always=10g:on",
+        ]);
+        let mut facts = Facts {
+            max_temp: Some(45.0),
+            ..Default::default()
+        };
+        facts.links.insert(String::from("eth0"), true);
+        let actions = evaluate(&rules, &facts);
+        let names: Vec<String> = actions.iter().map(|v| v.to_string()).collect();
+        assert_eq!(names, vec!["green", "10g:on"]);
+
+        facts.md_degraded = parse_mdstat(MDSTAT_DEGRADED);
+        facts.fans_ng = vec![0];
+        facts.links.insert(String::from("eth2"), false);
+        let actions = evaluate(&rules, &facts);
+        let names: Vec<String> = actions.iter().map(|v| v.to_string()).collect();
+        assert_eq!(names, vec!["blink-red", "10g:off"]);
+
+        facts.md_degraded.clear();
+        let actions = evaluate(&rules, &facts);
+        assert_eq!(actions[0].to_string(), "red");
+        facts.fans_ng.clear();
+        facts.max_temp = Some(61.0);
+        let actions = evaluate(&rules, &facts);
+        assert_eq!(actions[0].to_string(), "blink-red");
+    }
+}
@@ -0,0 +1,150 @@
+use super::action::Action;
+use crate::utils;
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use qute_ctrl::{LedControl, LedState};
+use std::{str::FromStr, thread::sleep, time::Duration};
+
+/// a LED action taking effect within a time of day, `<start>-<end>=<action>`, e.g.
+/// `22:00-07:00=brightness:0`; the range may wrap over midnight
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub action: Action,
+}
+
+impl Rule {
+    pub fn is_active(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = || -> Result<Rule> {
+            let mut parts = s.trim().splitn(2, '=');
+            let range = parts.next().unwrap_or_default();
+            let action = parts
+                .next()
+                .ok_or_else(|| anyhow!("require an action, e.g. =brightness:0"))?;
+            let mut times = range.splitn(2, '-');
+            let time = |v: Option<&str>| -> Result<NaiveTime> {
+                let v = v.ok_or_else(|| anyhow!("require a time range, e.g. 22:00-07:00"))?;
+                Ok(NaiveTime::parse_from_str(v.trim(), "%H:%M")?)
+            };
+            Ok(Rule {
+                start: time(times.next())?,
+                end: time(times.next())?,
+                action: action.parse()?,
+            })
+        };
+        parse().with_context(|| format!("invalid LED schedule: {}", s))
+    }
+}
+
+/// LED rules by time of day; later rules win
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub rules: Vec<Rule>,
+}
+
+impl Schedule {
+    /// LED configuration at the time, with active rules applied to the base configuration
+    pub fn state_at(&self, base: &LedState, time: NaiveTime) -> LedState {
+        let mut state = *base;
+        for rule in self.rules.iter().filter(|v| v.is_active(time)) {
+            rule.action.apply_to(&mut state);
+        }
+        state
+    }
+
+    /// apply the schedule until terminated, then restore LEDs
+    pub fn run<T: LedControl>(&self, chip: &T) -> Result<()> {
+        let base = chip.get_led_state()?;
+        let mut last = base;
+        while !utils::is_terminated() {
+            let state = self.state_at(&base, Local::now().time());
+            if state != last {
+                chip.set_led_state(&state)?;
+                println!(
+                    "{}\t√ LEDs were set by schedule",
+                    Local::now().format("%Y-%m-%d %H:%M:%S")
+                );
+                last = state;
+            }
+            sleep(Duration::from_secs(1));
+        }
+        chip.set_led_state(&base)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qute_ctrl::{LedColor, LedMode, StatusLed};
+
+    fn at(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn base() -> LedState {
+        LedState {
+            status: StatusLed {
+                mode: LedMode::On,
+                color: LedColor::Green,
+            },
+            brightness: 200,
+            front_usb: 0,
+            enclosure_ident: false,
+            fan: LedColor::Auto,
+            bbu: (false, false),
+            nic_10g: false,
+        }
+    }
+
+    #[test]
+    fn parse_rule() {
+        let rule: Rule = "22:00-07:00=brightness:0".parse().unwrap();
+        assert_eq!(rule.start, at(22, 0));
+        assert_eq!(rule.action, Action::Brightness(0));
+        assert!("22:00=off".parse::<Rule>().is_err());
+        assert!("22:00-25:00=off".parse::<Rule>().is_err());
+        assert!("22:00-07:00".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn wrap_over_midnight() {
+        let rule: Rule = "22:00-07:00=off".parse().unwrap();
+        assert!(rule.is_active(at(23, 30)));
+        assert!(rule.is_active(at(6, 59)));
+        assert!(!rule.is_active(at(7, 0)));
+        assert!(!rule.is_active(at(12, 0)));
+        let rule: Rule = "09:00-17:00=off".parse().unwrap();
+        assert!(rule.is_active(at(9, 0)));
+        assert!(!rule.is_active(at(17, 0)));
+    }
+
+    #[test]
+    fn state_by_time() {
+        let schedule = Schedule {
+            rules: vec![
+                "22:00-07:00=brightness:0".parse().unwrap(),
+                "23:00-06:00=off".parse().unwrap(),
+            ],
+        };
+        let base = base();
+        assert_eq!(schedule.state_at(&base, at(12, 0)), base);
+        let state = schedule.state_at(&base, at(22, 30));
+        assert_eq!(state.brightness, 0);
+        assert_eq!(state.status, base.status);
+        let state = schedule.state_at(&base, at(1, 0));
+        assert_eq!(state.status.mode, LedMode::Off);
+    }
+}
@@ -1,5 +1,3 @@
-#![feature(never_type)]
-
 //#![deny(missing_docs, warnings)]
 #[macro_use]
 extern crate log;
@@ -11,13 +9,17 @@ extern crate anyhow;
 pub(crate) mod cmd;
 pub(crate) mod config;
 pub(crate) mod ctx;
+pub(crate) mod disk;
+pub(crate) mod fan;
+pub(crate) mod led;
+pub(crate) mod state;
 pub(crate) mod utils;
 
-use crate::config::{Config, FanConfig};
+use crate::config::Config;
 use crate::ctx::{Context as PlatformContext, Options};
 use anyhow::{Context, Result};
 use pico_args::Arguments;
-use std::alloc::System;
+use std::{alloc::System, path::PathBuf};
 
 #[global_allocator]
 static A: System = System;
@@ -27,12 +29,22 @@ pub(crate) const APP_VERSION: &str = "0.1";
 
 fn main() -> Result<()> {
     let mut args = Arguments::from_env();
+    let path: Option<PathBuf> = args
+        .opt_value_from_str("--config")
+        .with_context(|| "invalid value for config")?;
+    // a broken config file fails the commands needing it, not help
+    let config = Config::load(path.as_deref());
+    let (quiet, verbose) = config
+        .as_ref()
+        .map(|v| (v.output.quiet, v.output.verbose))
+        .unwrap_or_default();
+    // options take precedence over the config file
     let opts = Options {
-        quiet: args.contains(["-q", "--quiet"]),
+        quiet: args.contains(["-q", "--quiet"]) || quiet,
         verbose: args
             .opt_value_from_str(["-v", "--verbose"])
             .with_context(|| "invalid value for verbose")?
-            .unwrap_or(0),
+            .unwrap_or(verbose),
         help: args.contains(["-h", "--help"]),
     };
     stderrlog::new()
@@ -44,12 +56,6 @@ fn main() -> Result<()> {
         .init()
         .unwrap();
 
-    let config = Config {
-        fan: vec![FanConfig {
-            max_speed: 1700,
-            min_speed: 0,
-        }],
-    };
     let ctx = PlatformContext::new(config, opts);
     run(&mut args, &ctx)
 }
@@ -63,8 +69,11 @@ fn run(args: &mut Arguments, ctx: &PlatformContext) -> Result<()> {
     //check sub command
     let text = args.subcommand().ok().flatten().unwrap_or_default();
     match text.as_str() {
+        "disk" => return cmd::disk::run(args, ctx),
         "eup" => return cmd::eup::run(args, ctx),
         "fan" => return cmd::fan::run(args, ctx),
+        "led" => return cmd::led::run(args, ctx),
+        "lifecycle" => return cmd::lifecycle::run(args, ctx),
         "power" => return cmd::power::run(args, ctx),
         "temp" => return cmd::temp::run(args, ctx),
         "monitor" => return cmd::monitor::run(args, ctx),
@@ -83,10 +92,14 @@ OPTIONS:
   -h, --help                      Show help message
   -v, --verbose [level:N]   Show verbose messages
   -q, --quiet                     Silence all output
+  --config [path]                Config file, default /etc/qute/config.json if exists
 
 COMMANDS:
+  disk                              list disk bays, locate disks
   eup                                get or set Eup mode
   fan                                 get or set fan speed
+  led                                 set LEDs
+  lifecycle                        show boot and shutdown on the status LED
   power                            get or set power recovery mode
   temp                              get temperature
   monitor                         auto adjust fan speed based on temperatures
@@ -0,0 +1,37 @@
+use anyhow::{Context, Result};
+use json::JsonValue;
+use std::{fs, path::PathBuf};
+
+/// directory of state files, which are kept across runs
+const STATE_DIR: &str = "/var/lib/qute";
+
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(STATE_DIR).join(name)
+}
+
+/// load state file; None if not exists
+pub fn load(name: &str) -> Result<Option<JsonValue>> {
+    let path = path(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read state file {}", path.display()))?;
+    let obj =
+        json::parse(&text).with_context(|| format!("invalid state file {}", path.display()))?;
+    Ok(Some(obj))
+}
+
+/// save state file
+pub fn save(name: &str, obj: &JsonValue) -> Result<()> {
+    let path = path(name);
+    fs::create_dir_all(STATE_DIR)
+        .with_context(|| format!("failed to create state directory {}", STATE_DIR))?;
+    // write to a temp file then rename, so that a crash will not leave a truncated file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, obj.pretty(2))
+        .with_context(|| format!("failed to write state file {}", tmp.display()))?;
+    fs::rename(&tmp, &path)
+        .with_context(|| format!("failed to write state file {}", path.display()))?;
+    Ok(())
+}
@@ -1,5 +1,45 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
 /// F=C×1.8+32
 /// C=(F-32)÷1.8
 pub fn temp_c2f(temp: f32) -> f32 {
     temp * 1.8 + 32.0
 }
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_terminate(_: libc::c_int) {
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+/// catch SIGINT and SIGTERM, so that long running commands get a chance to clean up
+pub fn catch_terminate() {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_terminate as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGTERM,
+            on_terminate as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// SIGINT or SIGTERM received or not
+pub fn is_terminated() -> bool {
+    TERMINATED.load(Ordering::SeqCst)
+}
+
+/// ask user to confirm, default no
+pub fn confirm(prompt: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let answer = line.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
@@ -0,0 +1,170 @@
+//drive bays on the front panel, and the disks in them
+use super::Disk;
+use crate::Result;
+use std::fs;
+use std::path::Path;
+
+/// a drive bay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bay {
+    /// number printed on the front panel, from 1
+    pub index: u8,
+    /// port id of EC, for LEDs and power of the bay
+    pub port_id: u8,
+    /// libata port the bay is wired to, e.g. 3 of `ata3`
+    pub ata_port: u32,
+    /// both ports were checked on the unit; otherwise a port is guessed from the position of the
+    /// bay, and may be the one of another bay
+    pub verified: bool,
+}
+
+/// ports a bay is wired to, as checked on the unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BayWiring {
+    /// libata port, e.g. 3 of `ata3`
+    pub ata_port: u32,
+    /// port id of EC; guessed from the position of the bay if None
+    pub port_id: Option<u8>,
+}
+
+/// drive bays from the wiring of each, from bay 1; a bay is verified only if its EC port id is
+/// known
+pub fn bays_of(wirings: &[BayWiring]) -> Vec<Bay> {
+    wirings
+        .iter()
+        .enumerate()
+        .map(|(i, wiring)| Bay {
+            index: i as u8 + 1,
+            port_id: wiring.port_id.unwrap_or(i as u8 + 1),
+            ata_port: wiring.ata_port,
+            verified: wiring.port_id.is_some(),
+        })
+        .collect()
+}
+
+/// drive bays guessed from libata ports under the sysfs root, bay n on the n-th port
+pub fn bays_in(sysfs: &Path) -> Result<Vec<Bay>> {
+    let mut ports = vec![];
+    let dir = sysfs.join("class/ata_port");
+    if dir.exists() {
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if let Some(port) = name.strip_prefix("ata").and_then(|v| v.parse().ok()) {
+                ports.push(port);
+            }
+        }
+    }
+    ports.sort_unstable();
+    let wirings: Vec<BayWiring> = ports
+        .into_iter()
+        .map(|ata_port| BayWiring {
+            ata_port,
+            port_id: None,
+        })
+        .collect();
+    Ok(bays_of(&wirings))
+}
+
+/// speed of the SATA link of a libata port under the sysfs root, e.g. `6.0 Gbps`; None if the
+/// link is down, i.e. no disk attached or the bay powered off
+pub fn link_speed_in(sysfs: &Path, ata_port: u32) -> Option<String> {
+    let path = sysfs.join(format!("class/ata_link/link{}/sata_spd", ata_port));
+    let speed = fs::read_to_string(path).ok()?;
+    match speed.trim() {
+        "" | "<unknown>" => None,
+        v => Some(v.to_owned()),
+    }
+}
+
+/// put disks into bays by libata port; disks out of bays, e.g. NVMe, are dropped
+pub fn match_bays(bays: &[Bay], disks: &[Disk]) -> Vec<(Bay, Option<Disk>)> {
+    bays.iter()
+        .map(|bay| {
+            let disk = disks
+                .iter()
+                .find(|v| v.ata_port == Some(bay.ata_port))
+                .cloned();
+            (*bay, disk)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::DiskKind;
+    use crate::testutil::TempDir;
+    use std::path::PathBuf;
+
+    fn sysfs(name: &str, ports: &[u32]) -> TempDir {
+        let root = TempDir::new(&format!("bay-{}", name));
+        for port in ports {
+            fs::create_dir_all(root.join(format!("class/ata_port/ata{}", port))).unwrap();
+        }
+        root
+    }
+
+    fn disk(name: &str, ata_port: Option<u32>) -> Disk {
+        Disk {
+            name: name.to_owned(),
+            dev: PathBuf::from("/dev").join(name),
+            kind: DiskKind::Sata,
+            model: None,
+            serial: None,
+            host: None,
+            ata_port,
+        }
+    }
+
+    fn wiring(ata_port: u32, port_id: Option<u8>) -> BayWiring {
+        BayWiring { ata_port, port_id }
+    }
+
+    #[test]
+    fn checked_layout() {
+        let bays = bays_of(&[wiring(3, Some(2)), wiring(4, None)]);
+        assert_eq!(
+            (bays[0].index, bays[0].ata_port, bays[0].port_id),
+            (1, 3, 2)
+        );
+        assert!(bays[0].verified);
+        // EC port id guessed
+        assert_eq!((bays[1].index, bays[1].port_id), (2, 2));
+        assert!(!bays[1].verified);
+    }
+
+    #[test]
+    fn generic_layout() {
+        let root = sysfs("generic", &[10, 2, 1]);
+        let bays = bays_in(&root).unwrap();
+        let ports: Vec<u32> = bays.iter().map(|v| v.ata_port).collect();
+        assert_eq!(ports, vec![1, 2, 10]);
+        assert_eq!((bays[2].index, bays[2].port_id), (3, 3));
+        assert!(bays.iter().all(|v| !v.verified));
+    }
+
+    #[test]
+    fn link_speed() {
+        let root = sysfs("link", &[]);
+        for (link, speed) in [(1, "6.0 Gbps\n"), (2, "<unknown>\n")].iter() {
+            root.write(&format!("class/ata_link/link{}/sata_spd", link), speed);
+        }
+        assert_eq!(link_speed_in(&root, 1).as_deref(), Some("6.0 Gbps"));
+        assert_eq!(link_speed_in(&root, 2), None);
+        assert_eq!(link_speed_in(&root, 3), None);
+    }
+
+    #[test]
+    fn match_disks() {
+        let bays = bays_of(&[wiring(1, None), wiring(2, None), wiring(3, None)]);
+        let disks = vec![
+            disk("sda", Some(3)),
+            disk("nvme0n1", None),
+            disk("sdb", Some(1)),
+        ];
+        let res = match_bays(&bays, &disks);
+        assert_eq!(res[0].1.as_ref().unwrap().name, "sdb");
+        assert!(res[1].1.is_none());
+        assert_eq!(res[2].1.as_ref().unwrap().name, "sda");
+    }
+}
@@ -0,0 +1,275 @@
+//physical disks, from sysfs block devices
+use crate::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiskKind {
+    Sata,
+    Sas,
+    Nvme,
+}
+
+impl fmt::Display for DiskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiskKind::Sata => write!(f, "SATA"),
+            DiskKind::Sas => write!(f, "SAS"),
+            DiskKind::Nvme => write!(f, "NVMe"),
+        }
+    }
+}
+
+/// a physical disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disk {
+    /// kernel name, e.g. `sda`
+    pub name: String,
+    /// e.g. `/dev/sda`
+    pub dev: PathBuf,
+    pub kind: DiskKind,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// SCSI host, e.g. 2 of `host2`
+    pub host: Option<u32>,
+    /// libata port, e.g. 3 of `ata3`; the SATA port the disk hangs off
+    pub ata_port: Option<u32>,
+}
+
+/// physical disks of the system, sorted by name
+pub fn list_disks() -> Result<Vec<Disk>> {
+    list_disks_in(Path::new("/sys"))
+}
+
+/// physical disks under the sysfs root; virtual, loop, zram, md, dm, removable and USB devices
+/// are excluded
+pub fn list_disks_in(sysfs: &Path) -> Result<Vec<Disk>> {
+    let dir = sysfs.join("block");
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut res = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(disk) = probe(&entry.path(), &name) {
+            res.push(disk);
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(res)
+}
+
+fn probe(block: &Path, name: &str) -> Option<Disk> {
+    // e.g. /sys/devices/pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0/block/sda
+    let path = fs::canonicalize(block).ok()?;
+    let path_str = path.to_string_lossy();
+    // with native NVMe multipath, the namespace head hangs off the subsystem, e.g.
+    // /sys/devices/virtual/nvme-subsystem/nvme-subsys0/nvme0n1, which holds model and serial
+    let head = path_str.contains("/devices/virtual/nvme-subsystem/");
+    let (device, physical) = if head {
+        let subsys = path.parent()?.to_path_buf();
+        let physical = has_physical_controller(&subsys);
+        (subsys, physical)
+    } else {
+        (block.join("device"), is_physical(&path_str))
+    };
+    if !physical || read_attr(block, "removable").as_deref() == Some("1") {
+        return None;
+    }
+    let kind = if name.starts_with("nvme") {
+        // multipath paths of a namespace are hidden
+        if read_attr(block, "hidden").as_deref() == Some("1") {
+            return None;
+        }
+        DiskKind::Nvme
+    } else {
+        // SCSI peripheral type 0: direct-access block device
+        if read_attr(&device, "type").as_deref() != Some("0") {
+            return None;
+        }
+        if path_str.contains("/end_device-") || path_str.contains("/sas_") {
+            DiskKind::Sas
+        } else if component(&path, "ata").is_some() {
+            DiskKind::Sata
+        } else {
+            return None;
+        }
+    };
+    let serial = match kind {
+        DiskKind::Nvme => read_attr(&device, "serial"),
+        _ => read_vpd_serial(&device.join("vpd_pg80")),
+    };
+    Some(Disk {
+        name: name.to_owned(),
+        dev: PathBuf::from("/dev").join(name),
+        kind,
+        model: read_attr(&device, "model"),
+        serial,
+        host: component(&path, "host"),
+        ata_port: component(&path, "ata"),
+    })
+}
+
+/// behind a PCI controller, and neither virtual nor USB
+fn is_physical(path: &str) -> bool {
+    if !path.contains("/devices/pci") || path.contains("/devices/virtual/") {
+        return false;
+    }
+    !path.contains("/usb") && !path.contains("/virtio")
+}
+
+/// whether a controller of the NVMe subsystem is physical; controllers are linked from the
+/// subsystem, e.g. `nvme-subsys0/nvme0`
+fn has_physical_controller(subsys: &Path) -> bool {
+    let entries = match fs::read_dir(subsys) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    entries
+        .filter_map(|v| v.ok())
+        .filter(|v| v.file_name().to_string_lossy().starts_with("nvme"))
+        .filter_map(|v| fs::canonicalize(v.path()).ok())
+        .any(|v| is_physical(&v.to_string_lossy()))
+}
+
+/// number of the path component with the prefix, e.g. 3 of `ata3`
+fn component(path: &Path, prefix: &str) -> Option<u32> {
+    path.components()
+        .filter_map(|v| v.as_os_str().to_str())
+        .find_map(|v| v.strip_prefix(prefix).and_then(|n| n.parse().ok()))
+}
+
+/// trimmed; None if missing or empty
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    let text = fs::read_to_string(dir.join(name)).ok()?;
+    Some(text.trim().to_owned()).filter(|v| !v.is_empty())
+}
+
+/// Unit Serial Number VPD page: 4 bytes header, then serial in ASCII
+fn read_vpd_serial(path: &Path) -> Option<String> {
+    let buf = fs::read(path).ok()?;
+    let len = *buf.get(3)? as usize;
+    let serial = buf.get(4..4 + len)?;
+    Some(String::from_utf8_lossy(serial).trim().to_owned()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::os::unix::fs::symlink;
+
+    /// fake sysfs tree
+    fn sysfs() -> TempDir {
+        let root = TempDir::new("block");
+        let add = |name: &str, device: &str, attrs: &[(&str, &[u8])]| {
+            let dev = root.join("devices").join(device);
+            let block = dev.join("block").join(name);
+            fs::create_dir_all(&block).unwrap();
+            fs::write(block.join("removable"), "0\n").unwrap();
+            if !attrs.is_empty() {
+                symlink(&dev, block.join("device")).unwrap();
+            }
+            for (path, value) in attrs {
+                fs::write(block.join(path), value).unwrap();
+            }
+            fs::create_dir_all(root.join("block")).unwrap();
+            symlink(&block, root.join("block").join(name)).unwrap();
+        };
+        let pci = "pci0000:00/0000:00:17.0";
+        add(
+            "sda",
+            &format!("{}/ata3/host2/target2:0:0/2:0:0:0", pci),
+            &[
+                ("device/type", b"0\n"),
+                ("device/model", b"WDC WD40EFRX-68N\n"),
+                ("device/vpd_pg80", b"\x00\x80\x00\x14     WD-WCC7K0ABCDEF"),
+            ],
+        );
+        add(
+            "sdb",
+            &format!(
+                "{}/0000:01:00.0/host0/port-0:0/end_device-0:0/target0:0:0/0:0:0:0",
+                pci
+            ),
+            &[("device/type", b"0\n"), ("device/model", b"ST4000NM0025\n")],
+        );
+        add(
+            "sr0",
+            &format!("{}/ata5/host4/target4:0:0/4:0:0:0", pci),
+            &[("device/type", b"5\n")],
+        );
+        add(
+            "sdc",
+            &format!("{}/usb1/1-1/1-1:1.0/host6/target6:0:0/6:0:0:0", pci),
+            &[("device/type", b"0\n")],
+        );
+        add(
+            "nvme0n1",
+            "pci0000:00/0000:00:1d.0/0000:03:00.0/nvme/nvme0",
+            &[
+                ("device/model", b"Samsung SSD 970 EVO Plus 1TB\n"),
+                ("device/serial", b"S4EWNX0R123456 \n"),
+            ],
+        );
+        add("loop0", "virtual/block", &[]);
+        add("md0", "virtual/block", &[]);
+        add("dm-0", "virtual/block", &[]);
+        add("zram0", "virtual/block", &[]);
+        // native NVMe multipath: the namespace head hangs off the subsystem, and the path of
+        // the controller is hidden
+        let subsys = root.join("devices/virtual/nvme-subsystem/nvme-subsys1");
+        let ctrl = root.join("devices/pci0000:00/0000:00:1c.0/0000:04:00.0/nvme/nvme1");
+        for (dev, hidden) in &[
+            (subsys.join("nvme1n1"), "0\n"),
+            (ctrl.join("nvme1c1n1"), "1\n"),
+        ] {
+            fs::create_dir_all(dev).unwrap();
+            fs::write(dev.join("removable"), "0\n").unwrap();
+            fs::write(dev.join("hidden"), hidden).unwrap();
+            let name = dev.file_name().unwrap();
+            symlink(dev, root.join("block").join(name)).unwrap();
+        }
+        fs::write(subsys.join("model"), "WD Red SN700 500GB\n").unwrap();
+        fs::write(subsys.join("serial"), "22123A456789\n").unwrap();
+        symlink(&ctrl, subsys.join("nvme1")).unwrap();
+        root
+    }
+
+    #[test]
+    fn list() {
+        let root = sysfs();
+        let disks = list_disks_in(&root).unwrap();
+        let names: Vec<&str> = disks.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["nvme0n1", "nvme1n1", "sda", "sdb"]);
+
+        let nvme = &disks[0];
+        assert_eq!(nvme.kind, DiskKind::Nvme);
+        assert_eq!(nvme.dev, PathBuf::from("/dev/nvme0n1"));
+        assert_eq!(nvme.serial.as_deref(), Some("S4EWNX0R123456"));
+        assert_eq!(nvme.ata_port, None);
+
+        let head = &disks[1];
+        assert_eq!(head.kind, DiskKind::Nvme);
+        assert_eq!(head.model.as_deref(), Some("WD Red SN700 500GB"));
+        assert_eq!(head.serial.as_deref(), Some("22123A456789"));
+
+        let sata = &disks[2];
+        assert_eq!(sata.kind, DiskKind::Sata);
+        assert_eq!(sata.model.as_deref(), Some("WDC WD40EFRX-68N"));
+        assert_eq!(sata.serial.as_deref(), Some("WD-WCC7K0ABCDEF"));
+        assert_eq!((sata.host, sata.ata_port), (Some(2), Some(3)));
+
+        let sas = &disks[3];
+        assert_eq!(sas.kind, DiskKind::Sas);
+        assert_eq!((sas.host, sas.ata_port), (Some(0), None));
+        assert_eq!(sas.serial, None);
+    }
+
+    #[test]
+    fn missing_sysfs() {
+        assert!(list_disks_in(Path::new("/nonexistent")).unwrap().is_empty());
+    }
+}
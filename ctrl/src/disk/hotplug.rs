@@ -0,0 +1,223 @@
+//taking disks offline and back, for hot-swap
+use super::{list_disks_in, Disk};
+use crate::{ffi, Error, Result};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::raw::{c_ulong, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// _IO(0x12, 97), flush buffer cache of a block device
+const BLKFLSBUF: c_ulong = 0x1261;
+
+/// the disk and its partitions, e.g. `sda`, `sda1`
+fn block_names(sysfs: &Path, name: &str) -> Result<Vec<String>> {
+    let mut res = vec![name.to_owned()];
+    let dir = sysfs.join("block").join(name);
+    for entry in fs::read_dir(&dir)? {
+        let part = entry?.file_name().to_string_lossy().into_owned();
+        if part.starts_with(name) {
+            res.push(part);
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+/// reasons why the disk must not be taken offline: mounted partitions, members of active md
+/// arrays, swap, and other holders such as device mapper; empty if not in use
+pub fn in_use(sysfs: &Path, procfs: &Path, disk: &Disk) -> Result<Vec<String>> {
+    let names = block_names(sysfs, &disk.name)?;
+    let is_ours = |dev: &str| {
+        dev.strip_prefix("/dev/")
+            .map(|v| names.iter().any(|n| n == v))
+            .unwrap_or(false)
+    };
+    let mut res = vec![];
+    let mounts = fs::read_to_string(procfs.join("mounts")).unwrap_or_default();
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        if let (Some(dev), Some(target)) = (fields.next(), fields.next()) {
+            if is_ours(dev) {
+                res.push(format!("{} is mounted on {}", dev, target));
+            }
+        }
+    }
+    let swaps = fs::read_to_string(procfs.join("swaps")).unwrap_or_default();
+    for line in swaps.lines().skip(1) {
+        if let Some(dev) = line.split_whitespace().next() {
+            if is_ours(dev) {
+                res.push(format!("{} is in use as swap", dev));
+            }
+        }
+    }
+    // md0 : active raid1 sdb1[1] sda1[0]
+    let mdstat = fs::read_to_string(procfs.join("mdstat")).unwrap_or_default();
+    for line in mdstat.lines() {
+        let mut fields = line.split_whitespace();
+        let (md, sep, state) = (fields.next(), fields.next(), fields.next());
+        if sep != Some(":") || state != Some("active") {
+            continue;
+        }
+        for member in fields {
+            let member = member.split('[').next().unwrap_or_default();
+            if names.iter().any(|n| n == member) {
+                res.push(format!(
+                    "/dev/{} is a member of active md array {}",
+                    member,
+                    md.unwrap_or_default()
+                ));
+            }
+        }
+    }
+    for name in names.iter() {
+        let dir = if name == &disk.name {
+            sysfs.join("block").join(name).join("holders")
+        } else {
+            sysfs
+                .join("block")
+                .join(&disk.name)
+                .join(name)
+                .join("holders")
+        };
+        for entry in fs::read_dir(&dir).into_iter().flatten() {
+            let holder = entry?.file_name().to_string_lossy().into_owned();
+            // md arrays are checked above, inactive ones do not matter
+            if !holder.starts_with("md") {
+                res.push(format!("/dev/{} is held by {}", name, holder));
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// write back dirty buffers of the block device
+pub fn flush(dev: &Path) -> Result<()> {
+    let file = OpenOptions::new().read(true).open(dev)?;
+    file.sync_all()?;
+    let ret = unsafe { ffi::ioctl(file.as_raw_fd(), BLKFLSBUF, std::ptr::null_mut::<c_void>()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// detach the SCSI device of the disk; the kernel flushes its cache and spins it down
+pub fn delete(sysfs: &Path, disk: &Disk) -> Result<()> {
+    fs::write(
+        sysfs.join("block").join(&disk.name).join("device/delete"),
+        "1",
+    )?;
+    Ok(())
+}
+
+/// SCSI host of the libata port, e.g. 2 of `ata3/host2`
+pub fn ata_host(sysfs: &Path, ata_port: u32) -> Option<u32> {
+    let dir = sysfs.join(format!("class/ata_port/ata{}/device", ata_port));
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .find_map(|v| {
+            let name = v.file_name().to_string_lossy().into_owned();
+            name.strip_prefix("host").and_then(|n| n.parse().ok())
+        })
+}
+
+/// scan the SCSI host of the libata port for new devices
+pub fn rescan(sysfs: &Path, ata_port: u32) -> Result<()> {
+    let host = ata_host(sysfs, ata_port)
+        .ok_or_else(|| Error::InvalidValue(format!("no SCSI host for ata{}", ata_port)))?;
+    fs::write(
+        sysfs.join(format!("class/scsi_host/host{}/scan", host)),
+        "- - -",
+    )?;
+    Ok(())
+}
+
+/// wait for a disk to appear on the libata port
+pub fn wait_for_disk(sysfs: &Path, ata_port: u32, timeout: Duration) -> Result<Disk> {
+    let start = Instant::now();
+    loop {
+        let disk = list_disks_in(sysfs)?
+            .into_iter()
+            .find(|v| v.ata_port == Some(ata_port));
+        if let Some(disk) = disk {
+            return Ok(disk);
+        }
+        if start.elapsed() >= timeout {
+            return Err(Error::Timeout(format!(
+                "no disk appeared on ata{} in {} s",
+                ata_port,
+                timeout.as_secs()
+            )));
+        }
+        sleep(Duration::from_millis(500));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::DiskKind;
+    use crate::testutil::TempDir;
+    use std::path::PathBuf;
+
+    fn root(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("hotplug-{}", name));
+        root.write("sys/block/sda/sda1/partition", "1\n");
+        root.write("sys/block/sda/sda2/partition", "2\n");
+        root.write("sys/block/sdb/sdb1/partition", "1\n");
+        fs::create_dir_all(root.join("sys/block/sda/holders")).unwrap();
+        root.write("sys/block/sdb/sdb1/holders/dm-0/dev", "253:0\n");
+        root.write(
+            "proc/mounts",
+            "/dev/nvme0n1p2 / ext4 rw 0 0\n/dev/sda1 /srv/data ext4 rw 0 0\n",
+        );
+        root.write(
+            "proc/swaps",
+            "Filename\tType\tSize\tUsed\tPriority\n/dev/sda2 partition 1048572 0 -2\n",
+        );
+        root.write(
+            "proc/mdstat",
+            "Personalities : [raid1]\nmd0 : active raid1 sdc1[1] sda1[0]\n      976630464 blocks [2/2] [UU]\n\nunused devices: <none>\n",
+        );
+        root
+    }
+
+    fn disk(name: &str) -> Disk {
+        Disk {
+            name: name.to_owned(),
+            dev: PathBuf::from("/dev").join(name),
+            kind: DiskKind::Sata,
+            model: None,
+            serial: None,
+            host: None,
+            ata_port: None,
+        }
+    }
+
+    #[test]
+    fn disk_in_use() {
+        let root = root("in-use");
+        let (sysfs, procfs) = (root.join("sys"), root.join("proc"));
+        let res = in_use(&sysfs, &procfs, &disk("sda")).unwrap();
+        assert_eq!(res.len(), 3);
+        assert!(res[0].contains("mounted on /srv/data"));
+        assert!(res[1].contains("swap"));
+        assert!(res[2].contains("md0"));
+        let res = in_use(&sysfs, &procfs, &disk("sdb")).unwrap();
+        assert_eq!(res, vec![String::from("/dev/sdb1 is held by dm-0")]);
+    }
+
+    #[test]
+    fn host_of_ata_port() {
+        let root = root("host");
+        let sysfs = root.join("sys");
+        fs::create_dir_all(sysfs.join("class/ata_port/ata3/device/host2")).unwrap();
+        fs::create_dir_all(sysfs.join("class/ata_port/ata3/device/link3")).unwrap();
+        assert_eq!(ata_host(&sysfs, 3), Some(2));
+        assert_eq!(ata_host(&sysfs, 4), None);
+    }
+}
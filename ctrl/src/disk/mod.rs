@@ -0,0 +1,143 @@
+//! physical disks and drive bays; SMART of ATA and NVMe disks, read natively via SG_IO and NVMe
+//! ioctls
+mod ata;
+mod bay;
+mod block;
+pub mod hotplug;
+mod nvme;
+mod stats;
+
+pub use ata::{
+    AtaSmart, Attribute, ATTR_OFFLINE_UNCORRECTABLE, ATTR_PENDING_SECTORS,
+    ATTR_REALLOCATED_SECTORS, ATTR_REALLOCATION_EVENTS,
+};
+pub use bay::{bays_in, bays_of, link_speed_in, match_bays, Bay, BayWiring};
+pub use block::{list_disks, list_disks_in, Disk, DiskKind};
+pub use nvme::NvmeSmart;
+pub use stats::{parse_diskstats, read_diskstats};
+
+use crate::Result;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Smart {
+    Ata(AtaSmart),
+    Nvme(NvmeSmart),
+}
+
+impl Smart {
+    /// current temperature in ℃
+    pub fn temperature(&self) -> Option<f32> {
+        match self {
+            Smart::Ata(v) => v.temperature(),
+            Smart::Nvme(v) => v.temperature,
+        }
+    }
+}
+
+/// power mode of disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerMode {
+    Active,
+    Idle,
+    /// spun down; reading SMART would spin it up
+    Standby,
+}
+
+impl PowerMode {
+    /// from count register of ATA CHECK POWER MODE
+    pub fn from_ata(count: u8) -> Self {
+        match count {
+            // standby_z, standby_y, NV cache with spindle spun down
+            0x00 | 0x01 | 0x40 => PowerMode::Standby,
+            0x80..=0x83 => PowerMode::Idle,
+            _ => PowerMode::Active,
+        }
+    }
+}
+
+impl fmt::Display for PowerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerMode::Active => write!(f, "active"),
+            PowerMode::Idle => write!(f, "idle"),
+            PowerMode::Standby => write!(f, "standby"),
+        }
+    }
+}
+
+/// `/dev/nvme0` or `/dev/nvme0n1`
+pub fn is_nvme(dev: &Path) -> bool {
+    dev.file_name()
+        .map(|v| v.to_string_lossy().starts_with("nvme"))
+        .unwrap_or(false)
+}
+
+fn open(dev: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        // do not block on removable devices without medium
+        .custom_flags(libc::O_NONBLOCK)
+        .open(dev)?;
+    Ok(file)
+}
+
+/// power mode of disk, without waking it up; NVMe disks are always active
+pub fn check_power_mode(dev: &Path) -> Result<PowerMode> {
+    if is_nvme(dev) {
+        return Ok(PowerMode::Active);
+    }
+    let file = open(dev)?;
+    Ok(PowerMode::from_ata(ata::check_power_mode(&file)?))
+}
+
+/// read SMART data of disk, e.g. `/dev/sda`, `/dev/nvme0`
+pub fn read_smart(dev: &Path) -> Result<Smart> {
+    let file = open(dev)?;
+    if is_nvme(dev) {
+        Ok(Smart::Nvme(nvme::read_smart_log(&file)?))
+    } else {
+        Ok(Smart::Ata(ata::read_smart_data(&file)?))
+    }
+}
+
+/// overall-health self-assessment of disk; NVMe disks pass if no critical warning
+pub fn read_smart_status(dev: &Path) -> Result<bool> {
+    let file = open(dev)?;
+    if is_nvme(dev) {
+        return Ok(nvme::read_smart_log(&file)?.critical_warning == 0);
+    }
+    ata::return_status(&file)
+}
+
+/// read current temperature of disk in ℃; None if the disk does not report one.
+///
+/// ATA disks are asked for SCT status first, which is more accurate than SMART attributes.
+pub fn read_temperature(dev: &Path) -> Result<Option<f32>> {
+    let file = open(dev)?;
+    if is_nvme(dev) {
+        return Ok(nvme::read_smart_log(&file)?.temperature);
+    }
+    match ata::read_sct_temperature(&file) {
+        Ok(Some(v)) => return Ok(Some(v)),
+        Ok(None) => {}
+        Err(e) => trace!("{}: SCT status not supported: {}", dev.display(), e),
+    }
+    Ok(ata::read_smart_data(&file)?.temperature())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_mode_from_ata() {
+        assert_eq!(PowerMode::from_ata(0x00), PowerMode::Standby);
+        assert_eq!(PowerMode::from_ata(0x40), PowerMode::Standby);
+        assert_eq!(PowerMode::from_ata(0x82), PowerMode::Idle);
+        assert_eq!(PowerMode::from_ata(0xff), PowerMode::Active);
+    }
+}
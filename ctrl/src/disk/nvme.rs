@@ -0,0 +1,137 @@
+//NVMe admin commands via NVME_IOCTL_ADMIN_CMD
+use crate::{ffi, Error, Result};
+use std::fs::File;
+use std::io;
+use std::os::raw::{c_ulong, c_void};
+use std::os::unix::io::AsRawFd;
+
+/// _IOWR('N', 0x41, struct nvme_admin_cmd)
+const NVME_IOCTL_ADMIN_CMD: c_ulong = 0xc048_4e41;
+const ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const LOG_SMART: u32 = 0x02;
+/// all namespaces
+const NSID_ALL: u32 = 0xffff_ffff;
+const LOG_SIZE: usize = 512;
+/// ms
+const TIMEOUT: u32 = 5000;
+
+#[repr(C)]
+#[derive(Default)]
+struct AdminCmd {
+    opcode: u8,
+    flags: u8,
+    rsvd1: u16,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    metadata: u64,
+    addr: u64,
+    metadata_len: u32,
+    data_len: u32,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+    timeout_ms: u32,
+    result: u32,
+}
+
+/// SMART / Health Information log of NVMe disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct NvmeSmart {
+    pub critical_warning: u8,
+    /// composite temperature in ℃
+    pub temperature: Option<f32>,
+    /// in percent
+    pub available_spare: u8,
+    pub available_spare_threshold: u8,
+    /// estimated percentage of life used, may exceed 100
+    pub percentage_used: u8,
+    pub media_errors: u64,
+}
+
+/// Get Log Page, SMART / Health Information
+pub fn read_smart_log(file: &File) -> Result<NvmeSmart> {
+    let mut buf = [0_u8; LOG_SIZE];
+    let numd = (LOG_SIZE / 4 - 1) as u32;
+    let mut cmd = AdminCmd {
+        opcode: ADMIN_GET_LOG_PAGE,
+        nsid: NSID_ALL,
+        addr: buf.as_mut_ptr() as u64,
+        data_len: LOG_SIZE as u32,
+        cdw10: (numd & 0xffff) << 16 | LOG_SMART,
+        cdw11: numd >> 16,
+        timeout_ms: TIMEOUT,
+        ..Default::default()
+    };
+    let ret = unsafe {
+        let ptr = &mut cmd as *mut AdminCmd as *mut c_void;
+        ffi::ioctl(file.as_raw_fd(), NVME_IOCTL_ADMIN_CMD, ptr)
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    // positive: NVMe status code
+    if ret > 0 {
+        return Err(Error::InvalidValue(format!(
+            "nvme: get log page failed, status {:#06x}",
+            ret
+        )));
+    }
+    parse_smart_log(&buf)
+}
+
+pub fn parse_smart_log(buf: &[u8]) -> Result<NvmeSmart> {
+    if buf.len() < LOG_SIZE {
+        return Err(Error::InvalidValue(format!(
+            "nvme: invalid SMART log length {}",
+            buf.len()
+        )));
+    }
+    // Kelvin; 0 if not reported
+    let kelvin = u16::from_le_bytes([buf[1], buf[2]]);
+    let temperature = match kelvin {
+        0 => None,
+        v => Some(v as f32 - 273.0),
+    };
+    let mut media_errors = [0_u8; 8];
+    media_errors.copy_from_slice(&buf[160..168]);
+    Ok(NvmeSmart {
+        critical_warning: buf[0],
+        temperature,
+        available_spare: buf[3],
+        available_spare_threshold: buf[4],
+        percentage_used: buf[5],
+        media_errors: u64::from_le_bytes(media_errors),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // synthetic, built from the spec layout rather than captured; see tests/fixtures/README.md
+    const SMART_LOG: &[u8] = include_bytes!("../../tests/fixtures/nvme-smart-log.bin");
+
+    #[test]
+    fn parse_log() {
+        let smart = parse_smart_log(SMART_LOG).unwrap();
+        assert_eq!(smart.critical_warning, 0);
+        assert_eq!(smart.temperature, Some(38.0));
+        assert_eq!(smart.available_spare, 100);
+        assert_eq!(smart.available_spare_threshold, 10);
+        assert_eq!(smart.percentage_used, 3);
+        assert_eq!(smart.media_errors, 0);
+    }
+
+    #[test]
+    fn parse_log_no_temperature() {
+        let mut buf = SMART_LOG.to_vec();
+        buf[1] = 0;
+        buf[2] = 0;
+        assert_eq!(parse_smart_log(&buf).unwrap().temperature, None);
+        assert!(parse_smart_log(&buf[..64]).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+//I/O counters of block devices
+use crate::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// completed reads and writes of each block device, from `<procfs>/diskstats`
+pub fn read_diskstats(procfs: &Path) -> Result<HashMap<String, u64>> {
+    let text = fs::read_to_string(procfs.join("diskstats"))?;
+    Ok(parse_diskstats(&text))
+}
+
+/// `major minor name reads merged sectors ms writes ...`
+pub fn parse_diskstats(text: &str) -> HashMap<String, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let reads: u64 = fields.get(3)?.parse().ok()?;
+            let writes: u64 = fields.get(7)?.parse().ok()?;
+            Some((fields[2].to_owned(), reads.wrapping_add(writes)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = "   8       0 sda 10421 2741 1218398 5120 3385 5032 98296 3905 0 7148 9548 0 0 0 0 311 523\n   8       1 sda1 10289 2741 1213630 5060 3383 5032 98296 3902 0 7108 8962 0 0 0 0 0 0\n 259       0 nvme0n1 58 0 3384 13 2 0 8 0 0 40 13\n   7       0 loop0 0 0\n";
+        let stats = parse_diskstats(text);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats["sda"], 10421 + 3385);
+        assert_eq!(stats["nvme0n1"], 60);
+        assert!(!stats.contains_key("loop0"));
+    }
+}
@@ -1,7 +1,14 @@
 use super::Feature;
-use crate::{hal::ec::Controller, Error, Result};
+use crate::{
+    hal::ec::Controller,
+    types::{FanCurve, FanMode, RpmHold},
+    Error, Result,
+};
 use std::io;
 
+/// first fan of each group; fans of a group share the mode register
+const FAN_GROUPS: [u8; 4] = [0, 6, 0x14, 0x1e];
+
 pub trait FanControl: Feature {
     fn get_fan_status(&self, fan_id: u8) -> Result<&'static str> {
         trace!("try to get fan status for fan {} by EC", fan_id);
@@ -83,13 +90,83 @@ pub trait FanControl: Feature {
         })
     }
 
+    /// one step of holding the fan at a target RPM: read its speed, and move PWM towards the
+    /// target unless within the tolerance. Call it again once the speed settled, about 2s, to
+    /// keep the speed as it drifts. Return the speed read and the PWM set
+    fn set_fan_rpm(&self, fan_id: u8, rpm: u16, tolerance: u16) -> Result<(u16, u8)> {
+        self.set_fan_rpm_within(fan_id, RpmHold::new(rpm, tolerance, 0, 0xff))
+    }
+
+    /// as `set_fan_rpm`, keeping PWM within the range of `hold`, e.g. above the stall floor
+    fn set_fan_rpm_within(&self, fan_id: u8, hold: RpmHold) -> Result<(u16, u8)> {
+        let pwm = self.get_fan_pwm(fan_id)?;
+        let speed = self.get_fan_speed(fan_id)?;
+        let next = hold.next(pwm, speed);
+        trace!(
+            "fan {}: {} RPM at pwm {}, pwm -> {}",
+            fan_id,
+            speed,
+            pwm,
+            next
+        );
+        if next != pwm {
+            self.set_fan_speed(fan_id, next)?;
+        }
+        Ok((speed, next))
+    }
+
+    /// get fan mode, auto: controlled by EC fw; manual: controlled by pwm
+    fn get_fan_mode(&self, fan_id: u8) -> Result<FanMode> {
+        trace!("try to get fan mode for fan {} by EC", fan_id);
+        let cmd = mode_cmd(fan_id)?;
+        self.with_ec(|ec| {
+            let value = ec.get_byte(cmd)?;
+            trace!("raw value of fan mode: {:#04x}", value);
+            Ok(value.into())
+        })
+    }
+
+    /// set fan mode; switch to auto to hand the fan back to EC fw
+    fn set_fan_mode(&self, fan_id: u8, mode: FanMode) -> Result<()> {
+        trace!("try to set fan mode to {} for fan {} by EC", mode, fan_id);
+        let cmd = mode_cmd(fan_id)?;
+        let value = match mode {
+            FanMode::Auto => 0x00,
+            FanMode::Manual => 0x10,
+            FanMode::Custom(..) => {
+                return Err(Error::InvalidValue(
+                    "fan control: custom fan mode is not supported".to_owned(),
+                ))
+            }
+        };
+        self.with_ec(|ec| ec.set_byte(cmd, value))
+    }
+
+    /// hand every fan back to EC fw, including fans unknown to the caller, by setting auto mode
+    /// of each group. Every group is tried even if some fail; return the first failure
+    fn hand_back_fans(&self) -> Result<()> {
+        let mut res = Ok(());
+        for &fan_id in FAN_GROUPS.iter() {
+            if let Err(e) = self.set_fan_mode(fan_id, FanMode::Auto) {
+                warn!(
+                    "fan control: failed to hand fans of fan {} back: {}",
+                    fan_id, e
+                );
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+
     fn get_fan_pwm(&self, fan_id: u8) -> Result<u8> {
         trace!("get pwm for fan {}", fan_id);
         let cmd = match fan_id {
             idx if idx <= 4 => 0x22e,
             idx if idx == 6 || idx == 7 => 0x24b,
-            idx if idx >= 0x14 && idx <= 0x19 => 0x22f,
-            idx if idx >= 0x1e && idx <= 0x23 => 0x23b,
+            idx if (0x14..=0x19).contains(&idx) => 0x22f,
+            idx if (0x1e..=0x23).contains(&idx) => 0x23b,
             idx => {
                 return Err(Error::InvalidValue(format!(
                     "fan control: invalid fan id {}",
@@ -105,26 +182,66 @@ pub trait FanControl: Feature {
         })
     }
 
+    /// get the slope of the Fan control of EC fw
+    fn get_fan_control_slope(&self, fan_id: u8) -> Result<u8> {
+        trace!("get control slope for fan {}", fan_id);
+        let cmd = slope_cmd(fan_id)?;
+        self.with_ec(|ec| ec.get_byte(cmd))
+    }
+
     /// set the slope of the Fan control of EC fw
     fn set_fan_control_slope(&self, fan_id: u8, slope: u8) -> Result<()> {
         trace!("set control slope to {} for fan {}", slope, fan_id);
-        let cmd = match fan_id {
-            idx if idx <= 4 => 0x296,
-            idx if idx == 6 || idx == 7 => 0x295,
-            idx if idx == 10 || idx == 0xb => {
-                return Err(Error::InvalidValue(format!(
-                    "fan control: set the fan slope to power fan {} is not supported",
-                    idx
-                )))
-            }
-            idx => {
-                return Err(Error::InvalidValue(format!(
-                    "fan control: invalid fan id {}",
-                    idx
-                )))
-            }
-        };
-
+        let cmd = slope_cmd(fan_id)?;
         self.with_ec(|ec| ec.set_byte(cmd, slope))
     }
+
+    /// get the fan curve of EC fw
+    fn get_fan_curve(&self, fan_id: u8) -> Result<FanCurve> {
+        let slope = self.get_fan_control_slope(fan_id)?;
+        Ok(FanCurve { slope })
+    }
+
+    /// set the fan curve of EC fw; only takes effect in auto fan mode
+    fn set_fan_curve(&self, fan_id: u8, curve: FanCurve) -> Result<()> {
+        self.set_fan_control_slope(fan_id, curve.slope)
+    }
+}
+
+/// register of fan mode
+fn mode_cmd(fan_id: u8) -> Result<u16> {
+    let cmd = match fan_id {
+        idx if idx <= 4 => 0x220,
+        idx if idx == 6 || idx == 7 => 0x223,
+        idx if (0x14..=0x19).contains(&idx) => 0x221,
+        idx if (0x1e..=0x23).contains(&idx) => 0x222,
+        idx => {
+            return Err(Error::InvalidValue(format!(
+                "fan control: invalid fan id {}",
+                idx
+            )))
+        }
+    };
+    Ok(cmd)
+}
+
+/// register of fan control slope, shared by the fans of the same group
+fn slope_cmd(fan_id: u8) -> Result<u16> {
+    let cmd = match fan_id {
+        idx if idx <= 4 => 0x296,
+        idx if idx == 6 || idx == 7 => 0x295,
+        idx if idx == 10 || idx == 0xb => {
+            return Err(Error::InvalidValue(format!(
+                "fan control: fan slope of power fan {} is not supported",
+                idx
+            )))
+        }
+        idx => {
+            return Err(Error::InvalidValue(format!(
+                "fan control: invalid fan id {}",
+                idx
+            )))
+        }
+    };
+    Ok(cmd)
 }
@@ -1,9 +1,81 @@
-use super::Feature;
-use crate::hal::ec::Controller;
-use crate::Result;
-use std::path::PathBuf;
-use std::process::Command;
-
-pub trait DiskDrive: Feature {
-    
+use super::{Feature, Power};
+use crate::{
+    disk::{self, hotplug, Bay, Disk},
+    Error, Result,
+};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// time for a disk to spin up and show up after power on
+const POWER_ON_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub trait DiskDrive: Feature + Power {
+    /// drive bays, guessed from libata ports
+    fn bays(&self) -> Result<Vec<Bay>> {
+        disk::bays_in(Path::new("/sys"))
+    }
+
+    /// drive bays, with the disks in them
+    fn bay_disks(&self) -> Result<Vec<(Bay, Option<Disk>)>> {
+        let bays = self.bays()?;
+        let disks = disk::list_disks()?;
+        Ok(disk::match_bays(&bays, &disks))
+    }
+
+    /// the bay holding the block device, e.g. `/dev/sdb`, or a link to it
+    fn find_bay(&self, dev: &Path) -> Result<Option<(Bay, Disk)>> {
+        let dev = fs::canonicalize(dev)?;
+        let res = self
+            .bay_disks()?
+            .into_iter()
+            .find_map(|(bay, disk)| disk.filter(|v| v.dev == dev).map(|v| (bay, v)));
+        Ok(res)
+    }
+
+    /// take the disk in the bay offline and cut its power; refuse if the disk is in use, or if
+    /// either port of the bay is guessed, as power of another bay holding a disk in use may be cut.
+    /// A bay where no disk is found is powered off only if `force`, since its disk can not be
+    /// checked
+    fn power_off_bay(&self, bay: &Bay, force: bool) -> Result<()> {
+        if !bay.verified {
+            return Err(Error::InvalidValue(format!(
+                "ports of bay {} are guessed, and may be the ones of another bay",
+                bay.index
+            )));
+        }
+        let sysfs = Path::new("/sys");
+        let disk = disk::list_disks_in(sysfs)?
+            .into_iter()
+            .find(|v| v.ata_port == Some(bay.ata_port));
+        if disk.is_none() && !force {
+            return Err(Error::InvalidValue(format!(
+                "no disk found in bay {}, whether it is in use is unknown",
+                bay.index
+            )));
+        }
+        if let Some(disk) = disk {
+            let reasons = hotplug::in_use(sysfs, Path::new("/proc"), &disk)?;
+            if !reasons.is_empty() {
+                return Err(Error::InvalidValue(format!(
+                    "{} in bay {} is in use: {}",
+                    disk.dev.display(),
+                    bay.index,
+                    reasons.join("; ")
+                )));
+            }
+            trace!("flush and detach {}", disk.dev.display());
+            hotplug::flush(&disk.dev)?;
+            hotplug::delete(sysfs, &disk)?;
+        }
+        self.sata_power_off(bay.port_id)
+    }
+
+    /// power on the bay, and wait for the disk in it to show up
+    fn power_on_bay(&self, bay: &Bay) -> Result<Disk> {
+        let sysfs = Path::new("/sys");
+        self.sata_power_on(bay.port_id)?;
+        hotplug::rescan(sysfs, bay.ata_port)?;
+        hotplug::wait_for_disk(sysfs, bay.ata_port, POWER_ON_TIMEOUT)
+    }
 }
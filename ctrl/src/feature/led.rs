@@ -1,7 +1,7 @@
 use super::Feature;
 use crate::{
     hal::ec::Controller,
-    types::{LedColor, LedMode},
+    types::{LedColor, LedMode, LedState, StatusLed},
     Error, Result,
 };
 
@@ -20,6 +20,25 @@ pub trait LedControl: Feature {
         })
     }
 
+    /// LED brightness
+    fn get_led_brightness(&self) -> Result<u8> {
+        self.with_ec(|ec| ec.get_byte(0x243))
+    }
+
+    /// color of fan LEDs; auto if driven by EC fw
+    fn get_fan_led(&self) -> Result<LedColor> {
+        self.with_ec(|ec| {
+            let value = ec.get_byte(0x16e)?;
+            if value & 0x80 == 0 {
+                return Ok(LedColor::Auto);
+            }
+            match value & 0x7f {
+                1 => Ok(LedColor::Red),
+                _ => Ok(LedColor::Green),
+            }
+        })
+    }
+
     ///  led_status: green, red, auto
     fn set_fan_led(&self, fan_id: u8, color: LedColor) -> Result<()> {
         if fan_id > 7 {
@@ -38,10 +57,18 @@ pub trait LedControl: Feature {
         self.with_ec(|ec| ec.set_byte(cmd, value))
     }
 
+    fn get_front_usb_led(&self) -> Result<u8> {
+        self.with_ec(|ec| ec.get_byte(0x154))
+    }
+
     fn set_front_usb_led(&self, v: u8) -> Result<()> {
         self.with_ec(|ec| ec.set_byte(0x154, v))
     }
 
+    fn get_status_led(&self) -> Result<StatusLed> {
+        self.with_ec(|ec| Ok(ec.get_byte(0x155)?.into()))
+    }
+
     /// set clear status of LED
     fn set_status_led(&self, color: LedColor, enable: bool) -> Result<()> {
         let v = match (color, enable) {
@@ -68,6 +95,10 @@ pub trait LedControl: Feature {
         self.with_ec(|ec| ec.set_byte(0x155, v))
     }
 
+    fn get_enclosure_ident_led(&self) -> Result<bool> {
+        self.with_ec(|ec| Ok(ec.get_byte(0x15e)? == 1))
+    }
+
     fn set_enclosure_ident_led(&self, enable: bool) -> Result<()> {
         let cmd = 0x15e;
         let status = if enable { 1 } else { 2 };
@@ -79,6 +110,20 @@ pub trait LedControl: Feature {
         self.with_ec(|ec| ec.set_byte(cmd, port_id))
     }
 
+    /// set activity LEDs of several ports, holding EC only once
+    fn set_disk_active_leds(&self, leds: &[(u8, bool)]) -> Result<()> {
+        if leds.is_empty() {
+            return Ok(());
+        }
+        self.with_ec(|ec| {
+            for &(port_id, enable) in leds {
+                let cmd = if enable { 0x15f } else { 0x157 };
+                ec.set_byte(cmd, port_id)?;
+            }
+            Ok(())
+        })
+    }
+
     fn set_disk_ident_led(&mut self, port_id: u8, enable: bool) -> Result<()> {
         let cmd = if enable { 0x158 } else { 0x159 };
         self.with_ec(|ec| ec.set_byte(cmd, port_id))
@@ -88,6 +133,14 @@ pub trait LedControl: Feature {
         self.with_ec(|ec| ec.set_byte(cmd, port_id))
     }
 
+    /// GPIO bbu status LED, (green, red); a cleared bit lights the LED
+    fn get_bbu_led(&self) -> Result<(bool, bool)> {
+        self.with_ec(|ec| {
+            let v = ec.get_byte(0x7d)?;
+            Ok((v & 1 == 0, v & 2 == 0))
+        })
+    }
+
     /// set GPIO bbu status  LED
     /// arg1: status = green, red, both
     /// arg2: enable = [01]
@@ -115,9 +168,44 @@ pub trait LedControl: Feature {
         self.with_ec(|ec| ec.set_byte(cmd, port_id))
     }
 
+    #[allow(non_snake_case)]
+    fn get_10G_led(&self) -> Result<bool> {
+        self.with_ec(|ec| Ok(ec.get_byte(0x167)? != 0))
+    }
+
     ///Turn off/on the 10G NIC present LED
     #[allow(non_snake_case)]
     fn set_10G_led(&self, enable: bool) -> Result<()> {
         self.with_ec(|ec| ec.set_byte(0x167, enable as u8))
     }
+
+    /// capture the LED configuration
+    fn get_led_state(&self) -> Result<LedState> {
+        Ok(LedState {
+            status: self.get_status_led()?,
+            brightness: self.get_led_brightness()?,
+            front_usb: self.get_front_usb_led()?,
+            enclosure_ident: self.get_enclosure_ident_led()?,
+            fan: self.get_fan_led()?,
+            bbu: self.get_bbu_led()?,
+            nic_10g: self.get_10G_led()?,
+        })
+    }
+
+    /// restore the LED configuration captured by `get_led_state`
+    fn set_led_state(&self, state: &LedState) -> Result<()> {
+        let status: u8 = state.status.into();
+        self.with_ec(|ec| ec.set_byte(0x155, status))?;
+        self.set_led_by_pwm(state.brightness)?;
+        self.set_front_usb_led(state.front_usb)?;
+        self.set_enclosure_ident_led(state.enclosure_ident)?;
+        self.set_fan_led(0, state.fan)?;
+        self.with_ec(|ec| {
+            let v = ec.get_byte(0x7d)? | 3;
+            let v = if state.bbu.0 { v & 0xfe } else { v };
+            let v = if state.bbu.1 { v & 0xfd } else { v };
+            ec.set_byte(0x7d, v)
+        })?;
+        self.set_10G_led(state.nic_10g)
+    }
 }
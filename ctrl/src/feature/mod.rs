@@ -10,6 +10,7 @@ pub trait Feature {
 mod eup;
 mod fan;
 mod fw;
+mod hdd;
 mod led;
 mod power;
 mod temp;
@@ -19,6 +20,7 @@ mod usb;
 pub use eup::EupControl;
 pub use fan::FanControl;
 pub use fw::Firmware;
+pub use hdd::DiskDrive;
 pub use led::LedControl;
 pub use power::Power;
 pub use temp::Temperature;
@@ -1,55 +1,104 @@
 use super::Feature;
-use crate::{hal::ec::Controller, Error, Result};
+use crate::{
+    hal::ec::Controller,
+    types::{CalibrationMode, SensorId, TempCalibration},
+    Error, Result,
+};
+
+/// values read from absent or broken sensors
+const SENTINELS: [u8; 3] = [0x00, 0x7f, 0xff];
 
 pub trait Temperature: Feature {
     fn get_temperature(&self, sensor_id: u8) -> Result<f32> {
         trace!("try to get temperature for cpu {} by EC", sensor_id);
-        // 0-4 cpu
-        // 5-9 sys
-        // 10-14 power
-        //15-38: env
-        let cmd: u16 = match sensor_id as u16 {
-            idx if idx <= 1 => idx + 0x600,
-            idx if idx >= 5 && idx <= 7 => idx + 0x5fd,
-            idx if idx == 10 => 0x659,
-            idx if idx == 0xb => 0x65c,
-            idx if idx >= 0xf && idx <= 0x26 => idx + 0x5f7,
-            idx => {
-                return Err(Error::InvalidValue(format!(
-                    "temperature: invalid sensor id {}",
-                    idx
-                )))
-            }
-        };
-
+        let cmd = sensor_cmd(sensor_id)?;
         self.with_ec(|ec| {
             let res = ec.get_byte(cmd)?;
             Ok(res as f32)
         })
     }
 
-    fn temperature_calibrate(&self, sensor_id: u8, arg1: bool, arg2: u32) -> Result<()> {
-        trace!(
-            "try to do temperature calibrate for cpu {} by EC: {}, {}",
-            sensor_id,
-            arg1,
-            arg2
-        );
+    /// get temperature of sensor; None if the sensor is absent or broken
+    fn get_sensor_temperature(&self, sensor: SensorId) -> Result<Option<f32>> {
+        trace!("try to get temperature for sensor {} by EC", sensor);
+        let cmd = sensor_cmd(sensor.0)?;
+        self.with_ec(|ec| {
+            let res = ec.get_byte(cmd)?;
+            if SENTINELS.contains(&res) || res > 0x7f {
+                trace!("sensor {}: implausible raw value {:#04x}", sensor, res);
+                return Ok(None);
+            }
+            Ok(Some(res as f32))
+        })
+    }
+
+    /// get temperature calibration state of EC fw
+    fn get_temperature_calibration(&self, mode: CalibrationMode) -> Result<TempCalibration> {
+        trace!("try to get temperature calibration {} by EC", mode);
+        let (bit, cmd) = calibration_cmd(mode);
         self.with_ec(|ec| {
             //cmd = 0x2e2
             let value = ec.get_byte(0x2e2)?;
-            let res = match (arg1, arg2) {
-                (false, 0) => value & 0xfe,
-                (false, _) => value | 1,
-                (true, 0) => value & 0xfd,
-                (true, _) => value | 2,
+            trace!("raw value of temperature calibration: {:#04x}", value);
+            let sensor = ec.get_byte(cmd)?;
+            Ok(TempCalibration {
+                mode,
+                sensor: SensorId(sensor),
+                enabled: value & bit != 0,
+            })
+        })
+    }
+
+    /// enable or disable temperature calibration of EC fw for the sensor
+    fn set_temperature_calibration(&self, calibration: TempCalibration) -> Result<()> {
+        trace!("try to set temperature calibration by EC: {}", calibration);
+        if calibration.sensor.kind().is_none() {
+            return Err(Error::InvalidValue(format!(
+                "temperature: invalid sensor id {}",
+                calibration.sensor.0
+            )));
+        }
+        let (bit, cmd) = calibration_cmd(calibration.mode);
+        self.with_ec(|ec| {
+            //cmd = 0x2e2
+            let value = ec.get_byte(0x2e2)?;
+            let res = if calibration.enabled {
+                value | bit
+            } else {
+                value & !bit
             };
             ec.set_byte(0x2e2, res)?;
-            let cmd = match (arg1, arg2) {
-                (true, _) => 0x27f,
-                (_, _) => 0x27d,
-            };
-            ec.set_byte(cmd, sensor_id)
+            ec.set_byte(cmd, calibration.sensor.0)
         })
     }
 }
+
+/// register of sensor
+fn sensor_cmd(sensor_id: u8) -> Result<u16> {
+    // 0-4 cpu
+    // 5-9 sys
+    // 10-14 power
+    //15-38: env
+    let cmd: u16 = match sensor_id as u16 {
+        idx if idx <= 1 => idx + 0x600,
+        idx if idx >= 5 && idx <= 7 => idx + 0x5fd,
+        idx if idx == 10 => 0x659,
+        idx if idx == 0xb => 0x65c,
+        idx if idx >= 0xf && idx <= 0x26 => idx + 0x5f7,
+        idx => {
+            return Err(Error::InvalidValue(format!(
+                "temperature: invalid sensor id {}",
+                idx
+            )))
+        }
+    };
+    Ok(cmd)
+}
+
+/// (enable bit at 0x2e2, sensor register) of calibration mode
+fn calibration_cmd(mode: CalibrationMode) -> (u8, u16) {
+    match mode {
+        CalibrationMode::Primary => (1, 0x27d),
+        CalibrationMode::Secondary => (2, 0x27f),
+    }
+}
@@ -5,7 +5,7 @@
 use super::status::Status;
 use crate::{
     hal::port::{open, Port},
-    Error, Result,
+    Error, PortBackend, Result,
 };
 use std::{
     cell::RefCell,
@@ -28,8 +28,11 @@ pub struct Device {
 
 impl Device {
     #[inline]
-    pub fn create(cmd_port: u16, data_port: u16) -> Result<Self> {
-        Ok(Self::new(open(cmd_port)?, open(data_port)?))
+    pub fn create(cmd_port: u16, data_port: u16, backend: PortBackend) -> Result<Self> {
+        Ok(Self::new(
+            open(cmd_port, backend)?,
+            open(data_port, backend)?,
+        ))
     }
 
     #[inline]
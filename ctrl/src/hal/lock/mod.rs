@@ -2,3 +2,4 @@ mod sem;
 
 pub use sem::Mutex;
 pub use sem::SemMutex;
+pub use sem::DEFAULT_TIMEOUT;
@@ -6,15 +6,28 @@ use std::ops::{Deref, DerefMut};
 use std::thread;
 use std::time::Duration;
 
+/// interval between attempts to take the lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+/// how long to wait for the lock by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(50);
+
 pub struct Mutex<T> {
     key: i32,
+    retries: u32,
     cell: UnsafeCell<T>,
 }
 
 impl<T> Mutex<T> {
     pub fn new(key: i32, data: T) -> Self {
+        Self::with_timeout(key, DEFAULT_TIMEOUT, data)
+    }
+
+    pub fn with_timeout(key: i32, timeout: Duration, data: T) -> Self {
+        let retries = timeout.as_millis() / RETRY_INTERVAL.as_millis();
         Self {
             key,
+            retries: (retries as u32).max(1),
             cell: UnsafeCell::new(data),
         }
     }
@@ -22,7 +35,7 @@ impl<T> Mutex<T> {
     pub fn lock(&self) -> Result<MutexGuard<'_, T>> {
         unsafe {
             if let Some(sem_id) = ffi::sem_init(self.key) {
-                for _ in 0..10 {
+                for _ in 0..self.retries {
                     if ffi::sem_try_wait(sem_id) {
                         trace!(
                             "sem mutex lock {} was taken for resource {:#08x}",
@@ -34,7 +47,7 @@ impl<T> Mutex<T> {
                             mutex: self,
                         });
                     }
-                    thread::sleep(Duration::from_millis(5));
+                    thread::sleep(RETRY_INTERVAL);
                 }
             }
         }
@@ -1,14 +1,12 @@
 use super::{Port, ReadByte, WriteByte};
-use crate::ffi;
-use libc;
+use crate::Result;
 use std::fmt;
 use std::fs;
-use std::io;
-use std::io::{Read, Seek, SeekFrom};
-use std::os::unix::fs::OpenOptionsExt;
-use std::os::unix::io::AsRawFd;
-use std::thread;
-use std::time::Duration;
+use std::io::{Error, ErrorKind};
+use std::os::unix::fs::FileExt;
+
+/// the kernel maps offsets of this file to I/O ports, requires CAP_SYS_RAWIO
+const DEV_PORT: &str = "/dev/port";
 
 pub struct FilePort {
     port: u16,
@@ -17,22 +15,16 @@ pub struct FilePort {
 
 impl FilePort {
     pub fn open(port: u16) -> Result<Self> {
-        unsafe {
-            //if libc::iopl(3) != 0 {
-            if ffi::ioperm(port.into(), 1, 1) != 0 {
-                return Err(format!("Failed to obtain io permission at port {:#04x}", port).into());
-            }
-        }
-        let file = "/dev/port";
-        let mut fp = fs::OpenOptions::new()
+        let fp = fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .custom_flags(libc::O_NONBLOCK)
-            .open(file)
-            .chain_err(|| format!("Failed to open port: {}", port))?;
-
-        fp.seek(SeekFrom::Start(port.into()))
-            .chain_err(|| format!("Failed to open port: {}", port))?;
+            .open(DEV_PORT)
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Failed to open {} for port {:#04x}: {}", DEV_PORT, port, e),
+                )
+            })?;
         Ok(Self { port, fp })
     }
 }
@@ -49,38 +41,29 @@ impl fmt::Display for FilePort {
     }
 }
 
-const WAIT_INTERVAL: u64 = 0x32;
 impl ReadByte for FilePort {
     fn read(&mut self) -> Result<u8> {
-        let buf = &mut [0_u8; 1];
-        //self.fp.read_exact(&mut buf[0..1])?;
-        for _ in 0..20 {
-            let count = unsafe {
-                libc::read(
-                    self.fp.as_raw_fd(),
-                    buf.as_mut_ptr() as *mut libc::c_void,
-                    buf.len(),
-                )
-            };
-            if count > 0 {
-                return Ok(buf[0]);
-            }
-            thread::sleep(Duration::from_millis(5));
+        let mut buf = [0_u8; 1];
+        // positional io, the file offset must stay at the port
+        if self.fp.read_at(&mut buf, self.port.into())? != 1 {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Failed to read port {:#04x}", self.port),
+            )
+            .into());
         }
-        Err("time out while reading a byte".into())
+        Ok(buf[0])
     }
 }
 
 impl WriteByte for FilePort {
     fn write(&mut self, value: u8) -> Result<()> {
-        let buf = &[value];
-        //self.fp.write(&mut buf)?;
-        unsafe {
-            libc::write(
-                self.fp.as_raw_fd(),
-                buf.as_ptr() as *const libc::c_void,
-                buf.len(),
-            );
+        if self.fp.write_at(&[value], self.port.into())? != 1 {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                format!("Failed to write port {:#04x}", self.port),
+            )
+            .into());
         }
         Ok(())
     }
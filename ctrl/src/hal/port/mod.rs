@@ -1,6 +1,5 @@
-use crate::Result;
+use crate::{PortBackend, Result};
 use std::fmt;
-use std::io;
 
 pub trait ReadByte {
     fn read(&mut self) -> Result<u8>;
@@ -15,19 +14,15 @@ pub trait Port: ReadByte + WriteByte + fmt::Display {
 }
 
 mod asm;
-//mod file;
+mod file;
 /// re-export
 pub use asm::AsmPort;
-//pub use file::FilePort;
+pub use file::FilePort;
 
 /// factory method
-pub fn open(port: u16) -> Result<Box<dyn Port>> {
-    // let is_root = unsafe { libc::geteuid() == 0 };
-    // if is_root {
-    let v = AsmPort::open(port)?;
-    return Ok(Box::new(v));
-    // }
-
-    //let v = FilePort::open(port)?;
-    //Ok(Box::new(v))
+pub fn open(port: u16, backend: PortBackend) -> Result<Box<dyn Port>> {
+    match backend {
+        PortBackend::Io => Ok(Box::new(AsmPort::open(port)?)),
+        PortBackend::File => Ok(Box::new(FilePort::open(port)?)),
+    }
 }
@@ -1,5 +1,5 @@
 use super::port::{open, Port};
-use crate::Result;
+use crate::{PortBackend, Result};
 
 pub struct Controller {
     index_port: Box<dyn Port>,
@@ -16,9 +16,9 @@ impl Controller {
             pnp: false,
         }
     }
-    pub fn create(index_port: u16, data_port: u16) -> Result<Self> {
-        let index_port = open(index_port)?;
-        let data_port = open(data_port)?;
+    pub fn create(index_port: u16, data_port: u16, backend: PortBackend) -> Result<Self> {
+        let index_port = open(index_port, backend)?;
+        let data_port = open(data_port, backend)?;
         Ok(Self::new(index_port, data_port))
     }
 
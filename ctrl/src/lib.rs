@@ -6,10 +6,14 @@ extern crate log;
 #[macro_use]
 extern crate thiserror;
 
+pub mod disk;
 pub(crate) mod feature;
 pub(crate) mod ffi;
 pub(crate) mod hal;
 pub mod platform;
+#[cfg(any(test, feature = "testutil"))]
+#[doc(hidden)]
+pub mod testutil;
 pub(crate) mod types;
 pub(crate) mod util;
 
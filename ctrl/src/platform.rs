@@ -1,16 +1,20 @@
 use super::feature::*;
 use crate::{
+    disk::{self, Bay, BayWiring},
     hal::{
         ec::{Controller, Device},
-        lock::Mutex,
+        lock::{Mutex, DEFAULT_TIMEOUT},
         sio::Controller as SuperIO,
     },
+    types::{PortBackend, SensorId},
     Error, Result,
 };
+use std::path::Path;
+use std::time::Duration;
 
-fn check_platform() -> Result<()> {
+fn check_platform(backend: PortBackend) -> Result<()> {
     //ITE 0x2E,0x2F or 0x4E, 0x4F
-    let mut sio = SuperIO::create(0x2E, 0x2F)?;
+    let mut sio = SuperIO::create(0x2E, 0x2F, backend)?;
     //sio.enter_pnp()?;
     //sio.select_logical_device(0x1)?;
     let id = sio.read_word(0x20)?; //0x20: id addr
@@ -23,29 +27,78 @@ fn check_platform() -> Result<()> {
     }
 }
 
+/// how to reach EC
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub cmd_port: u16,
+    pub data_port: u16,
+    pub backend: PortBackend,
+    /// key of the semaphore guarding EC
+    pub lock_key: i32,
+    /// how long to wait for the semaphore
+    pub lock_timeout: Duration,
+    /// wiring of each drive bay from bay 1, as checked on the unit; bays are guessed if None
+    pub bays: Option<Vec<BayWiring>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            cmd_port: 0x6c,
+            data_port: 0x68,
+            backend: PortBackend::Io,
+            lock_key: 0x4543, // 'ec', qnap use it, if you want to run it in QTS system
+            lock_timeout: DEFAULT_TIMEOUT,
+            bays: None,
+        }
+    }
+}
+
 pub struct Platform {
     ec: Mutex<Device>,
+    bays: Option<Vec<BayWiring>>,
 }
 
 impl Platform {
     #[inline]
     pub fn new(ec: Device) -> Self {
-        const KEY: i32 = 0x4543; // 'ec', qnap use it, if you want to run it in QTS system
+        let opts = Options::default();
         Self {
-            ec: Mutex::new(KEY, ec),
+            ec: Mutex::with_timeout(opts.lock_key, opts.lock_timeout, ec),
+            bays: opts.bays,
         }
     }
 
     pub fn with_ports(cmd_port: u16, data_port: u16) -> Result<Self> {
-        check_platform()?;
-        let ec = Device::create(cmd_port, data_port)?;
-        Ok(Self::new(ec))
+        Self::with_options(&Options {
+            cmd_port,
+            data_port,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_options(opts: &Options) -> Result<Self> {
+        check_platform(opts.backend)?;
+        let ec = Device::create(opts.cmd_port, opts.data_port, opts.backend)?;
+        Ok(Self {
+            ec: Mutex::with_timeout(opts.lock_key, opts.lock_timeout, ec),
+            bays: opts.bays.clone(),
+        })
     }
 
     pub fn with_default() -> Result<Self> {
-        const CMD_PROT: u16 = 0x6c;
-        const DATA_PORT: u16 = 0x68;
-        Self::with_ports(CMD_PROT, DATA_PORT)
+        Self::with_options(&Options::default())
+    }
+
+    /// probe temperature sensors, return the ones giving plausible values
+    pub fn sensors(&self) -> Result<Vec<SensorId>> {
+        let mut res = vec![];
+        for sensor in SensorId::all() {
+            if self.get_sensor_temperature(sensor)?.is_some() {
+                res.push(sensor);
+            }
+        }
+        Ok(res)
     }
 }
 
@@ -60,6 +113,15 @@ impl Feature for Platform {
     }
 }
 
+impl DiskDrive for Platform {
+    fn bays(&self) -> Result<Vec<Bay>> {
+        match &self.bays {
+            Some(wirings) => Ok(disk::bays_of(wirings)),
+            None => disk::bays_in(Path::new("/sys")),
+        }
+    }
+}
+
 impl EupControl for Platform {}
 
 impl FanControl for Platform {}
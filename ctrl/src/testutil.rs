@@ -0,0 +1,38 @@
+//! helpers shared by unit tests, of this crate and of those enabling feature `testutil`
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+/// directory under the temp dir, e.g. a fake sysfs tree; removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` tells apart tests of the process running in parallel
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("qute-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// write a file, creating its parent directories
+    pub fn write<C: AsRef<[u8]>>(&self, path: &str, contents: C) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
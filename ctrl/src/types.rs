@@ -19,13 +19,289 @@ impl From<LedColor> for u8 {
     }
 }
 
+impl FromStr for LedColor {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        let color = match t.as_str() {
+            "green" => LedColor::Green,
+            "red" => LedColor::Red,
+            "auto" => LedColor::Auto,
+            _ => {
+                return Err(Error::InvalidValue(
+                    "invalid input, must be one of green|red|auto".to_owned(),
+                ))
+            }
+        };
+        Ok(color)
+    }
+}
+
+impl fmt::Display for LedColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedColor::Green => write!(f, "green"),
+            LedColor::Red => write!(f, "red"),
+            LedColor::Auto => write!(f, "auto"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FanMode {
+    /// fan is driven by the fan curve of EC firmware
     Auto,
+    /// fan is driven by the PWM set by host
     Manual,
     Custom(Option<u32>, Option<u32>),
 }
 
+impl From<u8> for FanMode {
+    fn from(v: u8) -> FanMode {
+        if v & 0x10 != 0 {
+            FanMode::Manual
+        } else {
+            FanMode::Auto
+        }
+    }
+}
+
+impl FromStr for FanMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        let mode = match t.as_str() {
+            "auto" => FanMode::Auto,
+            "manual" => FanMode::Manual,
+            _ => {
+                return Err(Error::InvalidValue(
+                    "invalid input, must be one of auto|manual".to_owned(),
+                ))
+            }
+        };
+        Ok(mode)
+    }
+}
+
+impl fmt::Display for FanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FanMode::Auto => write!(f, "auto"),
+            FanMode::Manual => write!(f, "manual"),
+            FanMode::Custom(..) => write!(f, "custom"),
+        }
+    }
+}
+
+/// automatic fan control parameters of EC fw, take effect in auto fan mode
+///
+/// QNAP does not publish the fan curve of EC fw; only the slope register is known, no register
+/// of temperature thresholds is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FanCurve {
+    /// raw value of the slope register; how EC fw maps it to PWM is not documented
+    pub slope: u8,
+}
+
+impl fmt::Display for FanCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slope={}", self.slope)
+    }
+}
+
+/// closed loop holding a fan at a target RPM, by stepping its PWM
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RpmHold {
+    target: u16,
+    /// allowed deviation from the target, in RPM
+    tolerance: u16,
+    min_pwm: u8,
+    max_pwm: u8,
+}
+
+impl RpmHold {
+    /// PWM is kept within `min_pwm`-`max_pwm`, so that the fan does not stall
+    pub fn new(target: u16, tolerance: u16, min_pwm: u8, max_pwm: u8) -> Self {
+        Self {
+            target,
+            tolerance,
+            min_pwm: min_pwm.min(max_pwm),
+            max_pwm,
+        }
+    }
+
+    /// PWM moved into the range
+    pub fn clamp(&self, pwm: u8) -> u8 {
+        pwm.max(self.min_pwm).min(self.max_pwm)
+    }
+
+    /// whether the speed is within the tolerance of the target
+    pub fn holds(&self, rpm: u16) -> bool {
+        (self.target as i32 - rpm as i32).abs() <= self.tolerance as i32
+    }
+
+    /// next PWM, from the PWM set and the speed it gives
+    pub fn next(&self, pwm: u8, rpm: u16) -> u8 {
+        if self.target == 0 {
+            return 0;
+        }
+        if self.holds(rpm) {
+            return self.clamp(pwm);
+        }
+        let (min, max) = (self.min_pwm as i32, self.max_pwm as i32);
+        let diff = self.target as i32 - rpm as i32;
+        // roughly 7 RPM per PWM; EC takes PWM in percent, so move at least 1%
+        let step = diff / 8;
+        let step = if step.abs() < 3 {
+            3 * diff.signum()
+        } else {
+            step
+        };
+        (pwm as i32 + step).max(min).min(max) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorKind {
+    Cpu,
+    System,
+    Power,
+    Environment,
+}
+
+impl fmt::Display for SensorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorKind::Cpu => write!(f, "cpu"),
+            SensorKind::System => write!(f, "sys"),
+            SensorKind::Power => write!(f, "power"),
+            SensorKind::Environment => write!(f, "env"),
+        }
+    }
+}
+
+/// id of EC temperature sensor
+/// - 0-1: cpu
+/// - 5-7: sys
+/// - 10-11: power
+/// - 15-38: env
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SensorId(pub u8);
+
+impl SensorId {
+    /// all sensors supported by EC, not necessarily present
+    pub fn all() -> Vec<SensorId> {
+        (0..=0x26)
+            .map(SensorId)
+            .filter(|v| v.kind().is_some())
+            .collect()
+    }
+
+    pub fn kind(&self) -> Option<SensorKind> {
+        match self.0 {
+            0..=1 => Some(SensorKind::Cpu),
+            5..=7 => Some(SensorKind::System),
+            10..=11 => Some(SensorKind::Power),
+            0xf..=0x26 => Some(SensorKind::Environment),
+            _ => None,
+        }
+    }
+
+    /// index of the sensor among the sensors of the same kind
+    pub fn index(&self) -> u8 {
+        match self.0 {
+            0..=4 => self.0,
+            5..=9 => self.0 - 5,
+            10..=14 => self.0 - 10,
+            _ => self.0 - 0xf,
+        }
+    }
+}
+
+/// label of sensor, e.g. cpu0, sys1, env3
+impl fmt::Display for SensorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            Some(kind) => write!(f, "{}{}", kind, self.index()),
+            None => write!(f, "sensor{}", self.0),
+        }
+    }
+}
+
+/// parse sensor id or label, e.g. 5 or sys0
+impl FromStr for SensorId {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        if let Ok(id) = t.parse::<u8>() {
+            let id = SensorId(id);
+            if id.kind().is_some() {
+                return Ok(id);
+            }
+        }
+        SensorId::all()
+            .into_iter()
+            .find(|v| v.to_string() == t)
+            .ok_or_else(|| Error::InvalidValue(format!("invalid sensor: {}", s)))
+    }
+}
+
+/// temperature calibration modes of EC fw;
+/// each mode has an enable bit at 0x2e2 and a register holding the sensor to calibrate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationMode {
+    /// enable bit 0, sensor register 0x27d
+    Primary,
+    /// enable bit 1, sensor register 0x27f
+    Secondary,
+}
+
+impl FromStr for CalibrationMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        let mode = match t.as_str() {
+            "primary" => CalibrationMode::Primary,
+            "secondary" => CalibrationMode::Secondary,
+            _ => {
+                return Err(Error::InvalidValue(
+                    "invalid input, must be one of primary|secondary".to_owned(),
+                ))
+            }
+        };
+        Ok(mode)
+    }
+}
+
+impl fmt::Display for CalibrationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationMode::Primary => write!(f, "primary"),
+            CalibrationMode::Secondary => write!(f, "secondary"),
+        }
+    }
+}
+
+/// temperature calibration state of EC fw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempCalibration {
+    pub mode: CalibrationMode,
+    pub sensor: SensorId,
+    pub enabled: bool,
+}
+
+impl fmt::Display for TempCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: sensor {}, {}",
+            self.mode,
+            self.sensor,
+            SwitchState::from(self.enabled)
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LedMode {
     Off,
@@ -33,6 +309,116 @@ pub enum LedMode {
     Blink,
 }
 
+impl FromStr for LedMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        let mode = match t.as_str() {
+            "off" => LedMode::Off,
+            "on" => LedMode::On,
+            "blink" => LedMode::Blink,
+            _ => {
+                return Err(Error::InvalidValue(
+                    "invalid input, must be one of off|on|blink".to_owned(),
+                ))
+            }
+        };
+        Ok(mode)
+    }
+}
+
+impl fmt::Display for LedMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedMode::Off => write!(f, "off"),
+            LedMode::On => write!(f, "on"),
+            LedMode::Blink => write!(f, "blink"),
+        }
+    }
+}
+
+/// state of the status LED, register 0x155 of EC fw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusLed {
+    pub mode: LedMode,
+    /// auto: alternating green and red, while blinking
+    pub color: LedColor,
+}
+
+impl From<u8> for StatusLed {
+    fn from(v: u8) -> StatusLed {
+        let (mode, color) = match v {
+            1 => (LedMode::On, LedColor::Red),
+            2 => (LedMode::On, LedColor::Green),
+            3 => (LedMode::Blink, LedColor::Red),
+            4 => (LedMode::Blink, LedColor::Green),
+            5 => (LedMode::Blink, LedColor::Auto),
+            _ => (LedMode::Off, LedColor::Green),
+        };
+        StatusLed { mode, color }
+    }
+}
+
+impl From<StatusLed> for u8 {
+    fn from(led: StatusLed) -> u8 {
+        match (led.mode, led.color) {
+            (LedMode::On, LedColor::Red) => 1,
+            (LedMode::On, LedColor::Green) => 2,
+            (LedMode::Blink, LedColor::Red) => 3,
+            (LedMode::Blink, LedColor::Green) => 4,
+            (LedMode::Blink, LedColor::Auto) => 5,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for StatusLed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            LedMode::Off => write!(f, "off"),
+            mode => write!(f, "{} {}", self.color, mode),
+        }
+    }
+}
+
+/// LED configuration kept in EC registers, to be restored after a temporary change.
+///
+/// LEDs of disk bays are set by commands of EC fw, which cannot be read back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LedState {
+    pub status: StatusLed,
+    pub brightness: u8,
+    /// raw value of EC fw
+    pub front_usb: u8,
+    pub enclosure_ident: bool,
+    /// LED of fans
+    pub fan: LedColor,
+    /// (green, red)
+    pub bbu: (bool, bool),
+    pub nic_10g: bool,
+}
+
+impl fmt::Display for LedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "status: {}", self.status)?;
+        writeln!(f, "brightness: {}", self.brightness)?;
+        writeln!(f, "front usb: {}", self.front_usb)?;
+        writeln!(
+            f,
+            "enclosure ident: {}",
+            SwitchState::from(self.enclosure_ident)
+        )?;
+        writeln!(f, "fan: {}", self.fan)?;
+        writeln!(
+            f,
+            "bbu: green {}, red {}",
+            SwitchState::from(self.bbu.0),
+            SwitchState::from(self.bbu.1)
+        )?;
+        write!(f, "10g: {}", SwitchState::from(self.nic_10g))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PowerRecoveryMode {
     /// keep state before power loss
@@ -168,3 +554,103 @@ impl From<ShutdownMode> for u8 {
         }
     }
 }
+
+/// how CPU I/O ports are accessed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortBackend {
+    /// `in`/`out` instructions, after `ioperm`
+    Io,
+    /// reads and writes of `/dev/port`
+    File,
+}
+
+impl fmt::Display for PortBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortBackend::Io => write!(f, "io"),
+            PortBackend::File => write!(f, "file"),
+        }
+    }
+}
+
+impl FromStr for PortBackend {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "io" => Ok(PortBackend::Io),
+            "file" => Ok(PortBackend::File),
+            _ => Err(Error::InvalidValue(
+                "invalid input, must be one of io|file".to_owned(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensor_id_all() {
+        let all = SensorId::all();
+        assert_eq!(all.len(), 2 + 3 + 2 + 24);
+        assert_eq!(all[0], SensorId(0));
+        assert_eq!(all[all.len() - 1], SensorId(0x26));
+    }
+
+    #[test]
+    fn sensor_id_label() {
+        assert_eq!(SensorId(1).to_string(), "cpu1");
+        assert_eq!(SensorId(5).to_string(), "sys0");
+        assert_eq!(SensorId(11).to_string(), "power1");
+        assert_eq!(SensorId(0x10).to_string(), "env1");
+    }
+
+    #[test]
+    fn sensor_id_from_str() {
+        assert_eq!("sys2".parse::<SensorId>().unwrap(), SensorId(7));
+        assert_eq!("10".parse::<SensorId>().unwrap(), SensorId(10));
+        assert!("3".parse::<SensorId>().is_err());
+        assert!("gpu0".parse::<SensorId>().is_err());
+    }
+
+    #[test]
+    fn led_from_str() {
+        assert_eq!(" Red".parse::<LedColor>().unwrap(), LedColor::Red);
+        assert_eq!("blink".parse::<LedMode>().unwrap(), LedMode::Blink);
+        assert_eq!(LedColor::Auto.to_string(), "auto");
+        assert!("blue".parse::<LedColor>().is_err());
+        assert!("flash".parse::<LedMode>().is_err());
+    }
+
+    #[test]
+    fn status_led_raw() {
+        for v in 0..=5_u8 {
+            assert_eq!(u8::from(StatusLed::from(v)), v);
+        }
+        assert_eq!(StatusLed::from(0x42).mode, LedMode::Off);
+        assert_eq!(StatusLed::from(5).to_string(), "auto blink");
+    }
+
+    #[test]
+    fn steps_towards_target() {
+        let hold = RpmHold::new(900, 50, 40, 255);
+        assert_eq!(hold.next(100, 700), 125);
+        assert_eq!(hold.next(100, 1100), 75);
+        // at least 1%
+        assert_eq!(RpmHold::new(900, 10, 40, 255).next(100, 885), 103);
+        // within the tolerance
+        assert_eq!(hold.next(100, 870), 100);
+        assert!(hold.holds(950) && !hold.holds(951));
+    }
+
+    #[test]
+    fn keeps_pwm_range() {
+        let hold = RpmHold::new(300, 50, 60, 200);
+        // never below the stall floor, even if the fan runs too fast there
+        assert_eq!(hold.next(62, 600), 60);
+        assert_eq!(hold.next(30, 300), 60);
+        assert_eq!(hold.next(195, 0), 200);
+        assert_eq!(RpmHold::new(0, 50, 60, 200).next(100, 500), 0);
+    }
+}